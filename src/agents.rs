@@ -0,0 +1,32 @@
+//! Agents choose an action for a position. They share the [`ActionFn`] signature, so any of them
+//! can be handed to [`crate::play::play_game`] or wrapped as a [`crate::play::Player`].
+
+use crate::quarto::{GameState, Piece};
+use crate::rng::thread_rng;
+use rand::Rng;
+
+pub use crate::montecarlo::{mcts_action, mcts_rave_action, primitive_monte_carlo_action};
+pub use crate::play::ActionFn;
+
+/// Places on a random empty cell and hands over a random unused piece, or claims an open line.
+pub fn random_action<const N: usize, const K: usize>(
+    state: &GameState<N, K>,
+) -> (Option<(usize, usize)>, Option<Piece>) {
+    if state.can_claim() {
+        return (None, None);
+    }
+    let mut rng = thread_rng();
+    let mut put: Option<(usize, usize)> = None;
+    if !state.is_first_turn() {
+        let actions = state.legal_placements();
+        put = Some(*actions.get(rng.gen::<usize>() % actions.len()).unwrap());
+    }
+    let mut select: Option<Piece> = None;
+    if (put.is_none() || !state.can_put_then_win(put.unwrap().0, put.unwrap().1))
+        && !state.is_last_turn()
+    {
+        let actions = state.legal_pieces();
+        select = Some(*actions.get(rng.gen::<usize>() % actions.len()).unwrap());
+    }
+    (put, select)
+}
@@ -0,0 +1,192 @@
+//! Ply by ply evaluations of a recorded game, run by `quarto analyze`.
+//!
+//! Each ply is scored for the player who made it, with the best action and with the played one:
+//! exactly by the solver once few enough squares are empty, and by an MCTS search before. The
+//! difference decides its [`Judgement`].
+
+use crate::montecarlo::mcts_search;
+use crate::play::{GameRecord, PLAYER_NAMES};
+use crate::quarto::{format_action, Action, RuleSet, State};
+use crate::solver::{solve_actions, WIN};
+use std::fmt;
+use std::fmt::Formatter;
+
+const SOLVER_EMPTY_LIMIT: usize = 7;
+
+const INACCURACY_THRESHOLD: f64 = 0.1;
+
+const BLUNDER_THRESHOLD: f64 = 0.3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Judgement {
+    Good,
+    Inaccuracy,
+    Blunder,
+}
+
+// Evaluations are expected scores of the player who made the action, 1.0 for a win and 0.5 for a
+// draw, before the action (with the best action) and after the played action.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlyAnalysis {
+    pub player: usize,
+    pub action: Action,
+    pub best_action: Action,
+    pub before: f64,
+    pub after: f64,
+    pub exact: bool,
+    pub judgement: Judgement,
+}
+
+impl PlyAnalysis {
+    pub fn loss(&self) -> f64 {
+        (self.before - self.after).max(0.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Analysis {
+    pub plies: Vec<PlyAnalysis>,
+    pub resigned: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rules: RuleSet,
+}
+
+// The value of the position for the player to move and the action that achieves it. Small
+// positions are solved exactly, the others take the most searched action of a fixed budget MCTS.
+fn evaluate(state: &State, playout_number: usize) -> (f64, Action, bool) {
+    if !state.is_first_turn() && state.empty_count() <= SOLVER_EMPTY_LIMIT {
+        let (action, value) = solve_actions(state)
+            .into_iter()
+            .max_by_key(|&(_, value)| value)
+            .unwrap();
+        return (value as f64 / WIN as f64, action, true);
+    }
+    let statistics = mcts_search(state, playout_number);
+    let best = statistics.iter().max_by_key(|s| s.trials).unwrap();
+    (best.value, best.action, false)
+}
+
+// The score of the player who just moved into `state`. Before and after values of a ply are both
+// taken this way so that the optimism of the root search does not count as a loss.
+fn score_after(state: &State, playout_number: usize) -> (f64, bool) {
+    if state.is_done() {
+        (state.reward(), true)
+    } else {
+        let (value, _, exact) = evaluate(state, playout_number);
+        (1.0 - value, exact)
+    }
+}
+
+impl Analysis {
+    pub fn analyze(record: &GameRecord, playout_number: usize) -> Self {
+        let mut states = vec![State::new().with_rules(record.rules)];
+        for &action in &record.actions {
+            let mut state = *states.last().unwrap();
+            state.apply_action(action);
+            states.push(state);
+        }
+
+        let mut plies = Vec::new();
+        for (i, &action) in record.actions.iter().enumerate() {
+            let (_, best_action, exact_best) = evaluate(&states[i], playout_number);
+            let (after, exact_after) = score_after(&states[i + 1], playout_number);
+            let (before, exact_before) = if best_action == action {
+                (after, exact_after)
+            } else {
+                let mut best_state = states[i];
+                best_state.apply_action(best_action);
+                score_after(&best_state, playout_number)
+            };
+            let mut ply = PlyAnalysis {
+                player: i % 2,
+                action,
+                best_action,
+                before,
+                after,
+                exact: exact_best && exact_before && exact_after,
+                judgement: Judgement::Good,
+            };
+            ply.judgement = if ply.loss() >= BLUNDER_THRESHOLD {
+                Judgement::Blunder
+            } else if ply.loss() >= INACCURACY_THRESHOLD {
+                Judgement::Inaccuracy
+            } else {
+                Judgement::Good
+            };
+            plies.push(ply);
+        }
+        Analysis {
+            plies,
+            resigned: record.resigned,
+            rules: record.rules,
+        }
+    }
+}
+
+// Written as a game record with the evaluations as comments, so it can be loaded again with
+// `GameRecord::load`.
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# quarto game v1")?;
+        if self.rules != RuleSet::default() {
+            writeln!(f, "rules {}", self.rules)?;
+        }
+        for (i, ply) in self.plies.iter().enumerate() {
+            write!(
+                f,
+                "{:<8} # {:>3}. {} {:.2} -> {:.2}{}",
+                format_action(ply.action),
+                i + 1,
+                PLAYER_NAMES[ply.player],
+                ply.before,
+                ply.after,
+                if ply.exact { " (exact)" } else { "" }
+            )?;
+            match ply.judgement {
+                Judgement::Good => {}
+                Judgement::Inaccuracy => write!(f, " inaccuracy")?,
+                Judgement::Blunder => write!(f, " blunder")?,
+            }
+            if ply.judgement != Judgement::Good {
+                write!(f, ", best {}", format_action(ply.best_action))?;
+            }
+            writeln!(f)?;
+        }
+        if let Some(player) = self.resigned {
+            writeln!(f, "resign {}", PLAYER_NAMES[player])?;
+        }
+
+        writeln!(f, "#")?;
+        for (player, name) in PLAYER_NAMES.iter().enumerate() {
+            let plies: Vec<&PlyAnalysis> = self
+                .plies
+                .iter()
+                .filter(|ply| ply.player == player)
+                .collect();
+            let total_loss: f64 = plies.iter().map(|ply| ply.loss()).sum();
+            writeln!(
+                f,
+                "# {}: {} actions, {} inaccuracies, {} blunders, average loss {:.3}",
+                name,
+                plies.len(),
+                plies
+                    .iter()
+                    .filter(|ply| ply.judgement == Judgement::Inaccuracy)
+                    .count(),
+                plies
+                    .iter()
+                    .filter(|ply| ply.judgement == Judgement::Blunder)
+                    .count(),
+                if plies.is_empty() {
+                    0.0
+                } else {
+                    total_loss / plies.len() as f64
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
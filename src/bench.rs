@@ -0,0 +1,213 @@
+//! Throughput of the rules and searches at fixed positions and seeds, run by `quarto bench`.
+//! Results can be saved as a baseline and later runs compared against it.
+
+use crate::montecarlo::{mcts_action, playout, primitive_monte_carlo_action};
+use crate::quarto::State;
+use crate::rng;
+use std::fs::File;
+use std::hint::black_box;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::time::{Duration, Instant};
+
+const HEADER: &str = "# quarto bench v1";
+
+/// The seed every benchmark starts from.
+pub const SEED: u64 = 0x5155_4152_544f;
+
+const SEARCH_PLAYOUTS: usize = 1000;
+
+// The first placement, a midgame with eight empty cells and an endgame with five.
+const POSITIONS: [&str; 3] = [
+    "..../..../..../.... BSSF",
+    "BSSFWCTHBCSH./..BSTHWCSF/.BCTFWSTF./WSSH... BCTH",
+    "BCSH..WCSH/WSTHBCSFBCTFBSTF/.BSSF../WCTFBCTHBSSHWCTH WSTF",
+];
+
+struct Benchmark {
+    name: &'static str,
+    // One iteration over the positions, returning the number of operations it made.
+    run: fn(&[State]) -> u64,
+}
+
+const BENCHMARKS: [Benchmark; 5] = [
+    Benchmark {
+        name: "can_put_then_win",
+        run: |states| {
+            let mut ops = 0;
+            for state in states {
+                for (h, w) in state.legal_placements() {
+                    black_box(state.can_put_then_win(h, w));
+                    ops += 1;
+                }
+            }
+            ops
+        },
+    },
+    Benchmark {
+        name: "legal_placements",
+        run: |states| {
+            for state in states {
+                black_box(state.legal_placements());
+            }
+            states.len() as u64
+        },
+    },
+    Benchmark {
+        name: "playout",
+        run: |states| {
+            for &state in states {
+                let mut state = state;
+                black_box(playout(&mut state));
+            }
+            states.len() as u64
+        },
+    },
+    Benchmark {
+        name: "primitive_monte_carlo_action",
+        run: |states| {
+            for state in states {
+                black_box(primitive_monte_carlo_action(state, SEARCH_PLAYOUTS));
+            }
+            (states.len() * SEARCH_PLAYOUTS) as u64
+        },
+    },
+    Benchmark {
+        name: "mcts_action",
+        run: |states| {
+            for state in states {
+                black_box(mcts_action(state, SEARCH_PLAYOUTS));
+            }
+            (states.len() * SEARCH_PLAYOUTS) as u64
+        },
+    },
+];
+
+/// The iterations a benchmark ran and the operations they made: placements checked, positions
+/// generated or playouts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+    pub name: String,
+    pub iterations: u64,
+    pub ops: u64,
+    pub elapsed: Duration,
+}
+
+impl Measurement {
+    pub fn iterations_per_sec(&self) -> f64 {
+        self.iterations as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn ops_per_sec(&self) -> f64 {
+        self.ops as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Runs every benchmark whose name contains `filter` for at least `min_time`, each from
+/// [`SEED`].
+pub fn run(filter: Option<&str>, min_time: Duration) -> Vec<Measurement> {
+    let states: Vec<State> = POSITIONS
+        .iter()
+        .map(|notation| State::from_notation(notation).unwrap())
+        .collect();
+    let mut measurements = Vec::new();
+    for benchmark in &BENCHMARKS {
+        if filter.is_some_and(|filter| !benchmark.name.contains(filter)) {
+            continue;
+        }
+        rng::seed(SEED);
+        let start = Instant::now();
+        let mut iterations = 0;
+        let mut ops = 0;
+        while iterations == 0 || start.elapsed() < min_time {
+            ops += (benchmark.run)(&states);
+            iterations += 1;
+        }
+        measurements.push(Measurement {
+            name: String::from(benchmark.name),
+            iterations,
+            ops,
+            elapsed: start.elapsed(),
+        });
+    }
+    measurements
+}
+
+/// A table of the measurements, with the change in operations per second against the baseline
+/// measurement of the same name when there is one.
+pub fn report(measurements: &[Measurement], baseline: Option<&[Measurement]>) -> String {
+    let mut report = format!(
+        "{:<30} {:>14} {:>14} {:>10}\n",
+        "benchmark", "iter/s", "ops/s", "change"
+    );
+    for measurement in measurements {
+        let change = baseline
+            .and_then(|baseline| baseline.iter().find(|b| b.name == measurement.name))
+            .map_or(String::from("-"), |b| {
+                format!(
+                    "{:+.1}%",
+                    (measurement.ops_per_sec() / b.ops_per_sec() - 1.0) * 100.0
+                )
+            });
+        report.push_str(&format!(
+            "{:<30} {:>14.1} {:>14.1} {:>10}\n",
+            measurement.name,
+            measurement.iterations_per_sec(),
+            measurement.ops_per_sec(),
+            change
+        ));
+    }
+    report
+}
+
+// A header line followed by one `name iterations ops nanoseconds` line per measurement.
+pub fn save(measurements: &[Measurement], path: &str) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", HEADER)?;
+    for m in measurements {
+        writeln!(
+            writer,
+            "{} {} {} {}",
+            m.name,
+            m.iterations,
+            m.ops,
+            m.elapsed.as_nanos()
+        )?;
+    }
+    writer.flush()
+}
+
+pub fn load(path: &str) -> io::Result<Vec<Measurement>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut measurements = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let measurement = parse_line(&line).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid benchmark line: {}", line),
+            )
+        })?;
+        measurements.push(measurement);
+    }
+    Ok(measurements)
+}
+
+fn parse_line(line: &str) -> Option<Measurement> {
+    let mut fields = line.split_whitespace();
+    let name = String::from(fields.next()?);
+    let iterations = fields.next()?.parse().ok()?;
+    let ops = fields.next()?.parse().ok()?;
+    let elapsed = Duration::from_nanos(fields.next()?.parse().ok()?);
+    if fields.next().is_some() {
+        return None;
+    }
+    Some(Measurement {
+        name,
+        iterations,
+        ops,
+        elapsed,
+    })
+}
@@ -0,0 +1,278 @@
+//! Warnings about the moves of a human player, shown before they are played when
+//! [`HumanPlayer::coach`](crate::play::HumanPlayer::coach) is set.
+//!
+//! Where completing a line wins, a move is flagged when it misses or does not call a win, or hands
+//! the opponent a winning piece. Otherwise, once few enough squares are empty, it is flagged when
+//! the solver finds that it gives away a better result.
+
+use crate::quarto::{format_action, format_cell, Action, Objective, Piece, State};
+use crate::solver::{solve_actions, DRAW, LOSE, WIN};
+
+const SOLVER_EMPTY_LIMIT: usize = 7;
+
+fn line_name(line: &[(usize, usize)]) -> String {
+    if line.iter().all(|&(h, _)| h == line[0].0) {
+        format!("row {}", line[0].0 + 1)
+    } else if line.iter().all(|&(_, w)| w == line[0].1) {
+        format!("column {}", (b'a' + line[0].1 as u8) as char)
+    } else if line.iter().all(|&(h, w)| h == w) {
+        format!(
+            "the a1-{} diagonal",
+            format_cell((State::SIZE - 1, State::SIZE - 1))
+        )
+    } else if line.iter().all(|&(h, w)| h + w == State::SIZE - 1) {
+        format!(
+            "the {}-{} diagonal",
+            format_cell((0, State::SIZE - 1)),
+            format_cell((State::SIZE - 1, 0))
+        )
+    } else {
+        format!("the square at {}", format_cell(line[0]))
+    }
+}
+
+// Names the lines through (h, w) that the piece in hand completes and the attributes they share.
+fn explain_win(state: &State, (h, w): (usize, usize)) -> String {
+    let mut board = *state;
+    board.put_piece(h, w);
+    let mut explanations = Vec::new();
+    for line in board.winning_lines() {
+        if !line.contains(&(h, w)) {
+            continue;
+        }
+        let pieces: Vec<Piece> = line
+            .iter()
+            .filter_map(|&(h, w)| board.get_piece(h, w))
+            .collect();
+        let shared: Vec<&str> = (0..4)
+            .filter(|&i| {
+                pieces
+                    .iter()
+                    .all(|p| p.attribute_names()[i] == pieces[0].attribute_names()[i])
+            })
+            .map(|i| pieces[0].attribute_names()[i])
+            .collect();
+        explanations.push(format!(
+            "{} would all be {}",
+            line_name(&line),
+            shared.join(", ")
+        ));
+    }
+    explanations.join("; ")
+}
+
+fn winning_place(state: &State) -> Option<(usize, usize)> {
+    state
+        .legal_placements()
+        .into_iter()
+        .find(|&(h, w)| state.can_put_then_win(h, w))
+}
+
+fn describe(value: u8) -> &'static str {
+    match value {
+        WIN => "wins",
+        DRAW => "draws",
+        _ => "loses",
+    }
+}
+
+pub fn review(state: &State, (place, piece): Action) -> Vec<String> {
+    let mut warnings = Vec::new();
+    // The checks of completed lines only hold where completing a line wins. Under misère only the
+    // solver judges the action.
+    let lines_win = state.rules().objective != Objective::Misere;
+
+    if let Some((h, w)) = place.filter(|_| lines_win) {
+        if state.can_put_then_win(h, w) {
+            // Under the call rule handing over a piece leaves the line for the opponent to claim,
+            // whichever piece it is.
+            if piece.is_some() {
+                warnings.push(format!(
+                    "you did not call quarto at {}: {}, and your opponent can claim it",
+                    format_cell((h, w)),
+                    explain_win(state, (h, w))
+                ));
+                return warnings;
+            }
+        } else if let Some(cell) = winning_place(state) {
+            warnings.push(format!(
+                "you missed a win at {}: {}",
+                format_cell(cell),
+                explain_win(state, cell)
+            ));
+        }
+    }
+
+    if let Some(piece) = piece.filter(|_| lines_win) {
+        let mut next_state = *state;
+        if let Some((h, w)) = place {
+            next_state.put_piece(h, w);
+        }
+        let gives_win = |piece: Piece| {
+            let mut child_state = next_state;
+            child_state.select_piece(piece);
+            winning_place(&child_state).map(|cell| (child_state, cell))
+        };
+        if let Some((child_state, cell)) = gives_win(piece) {
+            let safe_pieces: Vec<String> = next_state
+                .legal_pieces()
+                .into_iter()
+                .filter(|&p| gives_win(p).is_none())
+                .map(|p| p.to_string())
+                .collect();
+            if !safe_pieces.is_empty() {
+                warnings.push(format!(
+                    "{} lets your opponent win at {}: {} (safe pieces: {})",
+                    piece,
+                    format_cell(cell),
+                    explain_win(&child_state, cell),
+                    safe_pieces.join(" ")
+                ));
+            }
+        }
+    }
+
+    if warnings.is_empty() && !state.is_first_turn() && state.empty_count() <= SOLVER_EMPTY_LIMIT {
+        let values = solve_actions(state);
+        let chosen = values
+            .iter()
+            .find(|&&(action, _)| action == (place, piece))
+            .map_or(LOSE, |&(_, value)| value);
+        if let Some(&(best_action, best)) = values.iter().max_by_key(|&&(_, value)| value) {
+            if best > chosen {
+                warnings.push(format!(
+                    "this {} with perfect play, but {} {}",
+                    describe(chosen),
+                    format_action(best_action),
+                    describe(best)
+                ));
+            }
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::random_action;
+    use crate::rng;
+
+    fn piece(code: &str) -> Piece {
+        code.parse().unwrap()
+    }
+
+    #[test]
+    fn missed_wins_name_their_line() {
+        let state = State::from_notation("BSTFBSTHBSSF./..../..../.... BCSH").unwrap();
+        let warnings = review(&state, (Some((3, 3)), Some(piece("WCTF"))));
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].starts_with("you missed a win at d1: row 1 would all be"));
+        assert!(review(&state, (Some((0, 3)), None)).is_empty());
+
+        let state = State::from_notation("...BSTF/..BSTH./.BSSF../.... BCSH").unwrap();
+        let warnings = review(&state, (Some((3, 3)), Some(piece("WCTF"))));
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(
+            warnings[0].starts_with("you missed a win at a4: the d1-a4 diagonal"),
+            "{}",
+            warnings[0]
+        );
+    }
+
+    #[test]
+    fn handing_over_a_winning_piece_is_flagged() {
+        let state = State::from_notation("BSTFBSTHBCTF./..../..../.... WSSH").unwrap();
+        let warnings = review(&state, (Some((3, 3)), Some(piece("BCSH"))));
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(
+            warnings[0].starts_with("BCSH lets your opponent win at "),
+            "{}",
+            warnings[0]
+        );
+        assert!(warnings[0].contains("WCSF"), "{}", warnings[0]);
+        assert!(review(&state, (Some((3, 3)), Some(piece("WCSF")))).is_empty());
+    }
+
+    #[test]
+    fn missed_calls_are_flagged() {
+        let state = State::from_notation("BSTFBSTHBSSF./..../..../.... BCSH call").unwrap();
+        let action = (Some((0, 3)), Some(piece("WCTF")));
+        assert!(state.is_legal_action(action));
+        let warnings = review(&state, action);
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(
+            warnings[0].starts_with("you did not call quarto at d1: row 1 would all be"),
+            "{}",
+            warnings[0]
+        );
+        assert!(warnings[0].ends_with("and your opponent can claim it"));
+        assert!(review(&state, (Some((0, 3)), None)).is_empty());
+    }
+
+    #[test]
+    fn misere_lines_are_left_to_the_solver() {
+        let state = State::from_notation("BSTFBSTHBSSF./..../..../.... BCSH misere").unwrap();
+        assert!(review(&state, (Some((3, 3)), Some(piece("WCTF")))).is_empty());
+
+        rng::seed(2);
+        let rules = "misere".parse().unwrap();
+        let mut blunders = 0;
+        for _ in 0..20 {
+            let mut state = State::new().with_rules(rules);
+            while !state.is_done() && state.empty_count() > 5 {
+                state.apply_action(random_action(&state));
+            }
+            if state.is_done() {
+                continue;
+            }
+            let values = solve_actions(&state);
+            let best = values.iter().map(|&(_, value)| value).max().unwrap();
+            for &(action, value) in &values {
+                let warnings = review(&state, action);
+                if value == best {
+                    assert!(warnings.is_empty(), "{} {:?}", state.notation(), warnings);
+                } else {
+                    assert_eq!(warnings.len(), 1, "{} {:?}", state.notation(), warnings);
+                    assert!(warnings[0].contains("perfect play"), "{}", warnings[0]);
+                    blunders += 1;
+                }
+            }
+        }
+        assert!(blunders > 0);
+    }
+
+    #[test]
+    fn solved_blunders_are_flagged() {
+        rng::seed(1);
+        let mut blunders = 0;
+        for _ in 0..20 {
+            let mut state = State::new();
+            // Below the solver's limit to keep the test fast.
+            while !state.is_done() && state.empty_count() > 5 {
+                state.apply_action(random_action(&state));
+            }
+            if state.is_done() {
+                continue;
+            }
+            let values = solve_actions(&state);
+            let best = values.iter().map(|&(_, value)| value).max().unwrap();
+            for &(action, value) in &values {
+                let warnings = review(&state, action);
+                if value == best {
+                    assert!(
+                        warnings.iter().all(|w| !w.contains("perfect play")),
+                        "{} {:?}",
+                        state.notation(),
+                        warnings
+                    );
+                } else {
+                    assert!(!warnings.is_empty(), "{} {:?}", state.notation(), action);
+                    blunders += 1;
+                }
+            }
+        }
+        assert!(blunders > 0);
+    }
+}
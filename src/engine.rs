@@ -0,0 +1,250 @@
+//! A long-running engine driven over stdin and stdout, in the spirit of UCI.
+//!
+//! ```text
+//! newgame                                  back to the initial position
+//! position startpos [moves <action> ...]
+//! position <notation> [moves <action> ...] see `State::notation` and `format_action`
+//! go [playouts N | movetime MS]            default 10000 playouts
+//! stop                                     ends the current search early
+//! isready                                  answered with `readyok` at once, even during a search
+//! quit                                     stops any search and exits
+//! ```
+//!
+//! A search answers with `info` lines followed by `bestmove <cell|-> <piece|->`, or `bestmove none`
+//! when the game is over. Problems are reported as `info string <message>`. Only MCTS searches
+//! report progress and stop early; the other agents search their playouts or the default number.
+
+use crate::agents::random_action;
+use crate::montecarlo::{
+    forced_action, mcts_rave_action, mcts_search_until, primitive_monte_carlo_action,
+    ActionStatistics, SearchLimit, RAVE_EQUIVALENCE,
+};
+use crate::quarto::{format_action, parse_action, Action, State};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const DEFAULT_PLAYOUTS: usize = 10000;
+
+#[derive(Clone, Copy)]
+pub enum Agent {
+    Mcts,
+    Rave,
+    Primitive,
+    Random,
+}
+
+impl Agent {
+    pub fn from_name(name: &str) -> Option<Agent> {
+        match name {
+            "mcts" => Some(Agent::Mcts),
+            "rave" => Some(Agent::Rave),
+            "primitive" => Some(Agent::Primitive),
+            "random" => Some(Agent::Random),
+            _ => None,
+        }
+    }
+}
+
+fn best_statistics(statistics: &[ActionStatistics]) -> Option<&ActionStatistics> {
+    statistics.iter().max_by_key(|s| s.trials)
+}
+
+fn info_line(playouts: usize, elapsed: Duration, statistics: &[ActionStatistics]) -> String {
+    let millis = elapsed.as_millis();
+    let mut line = format!(
+        "info playouts {} time {} nps {}",
+        playouts,
+        millis,
+        playouts as u128 * 1000 / millis.max(1)
+    );
+    if let Some(best) = best_statistics(statistics) {
+        line.push_str(&format!(
+            " value {:.3} visits {} best {}",
+            best.value,
+            best.trials,
+            format_action(best.action)
+        ));
+    }
+    line
+}
+
+fn search<W: Write>(
+    agent: Agent,
+    state: &State,
+    limit: &SearchLimit,
+    stop: &AtomicBool,
+    output: &Mutex<W>,
+) -> Action {
+    let playout_number = match *limit {
+        SearchLimit::Playouts(playout_number) => playout_number,
+        SearchLimit::Time(_) => DEFAULT_PLAYOUTS,
+    };
+    match agent {
+        Agent::Mcts => {
+            if let Some(action) = forced_action(state) {
+                return action;
+            }
+            let start = Instant::now();
+            let statistics = mcts_search_until(state, limit, stop, &mut |playouts, statistics| {
+                let line = info_line(playouts, start.elapsed(), statistics);
+                let _ = writeln!(output.lock().unwrap(), "{}", line);
+            });
+            let playouts = statistics.iter().map(|s| s.trials as usize).sum();
+            let line = info_line(playouts, start.elapsed(), &statistics);
+            let _ = writeln!(output.lock().unwrap(), "{}", line);
+            best_statistics(&statistics).unwrap().action
+        }
+        Agent::Rave => mcts_rave_action(state, playout_number, RAVE_EQUIVALENCE),
+        Agent::Primitive => primitive_monte_carlo_action(state, playout_number),
+        Agent::Random => random_action(state),
+    }
+}
+
+fn parse_position(words: &[&str]) -> Result<State, String> {
+    let end = words
+        .iter()
+        .position(|&word| word == "moves")
+        .unwrap_or(words.len());
+    let (position, rest) = words.split_at(end);
+    let mut state = match position {
+        ["startpos"] => State::new(),
+        [] => return Err(String::from("position needs startpos or a notation")),
+        _ => {
+            let notation = position.join(" ");
+            State::from_notation(&notation)
+                .ok_or_else(|| format!("invalid position: {}", notation))?
+        }
+    };
+    let moves = match rest {
+        [] => return Ok(state),
+        ["moves", moves @ ..] if moves.len() % 2 == 0 => moves,
+        _ => return Err(String::from("moves come in `<cell|-> <piece|->` pairs")),
+    };
+    for pair in moves.chunks(2) {
+        let text = pair.join(" ");
+        match parse_action(&text).map(|action| state.normalize_action(action)) {
+            Some(action) if state.is_legal_action(action) => state.apply_action(action),
+            _ => return Err(format!("illegal move: {}", text)),
+        }
+    }
+    Ok(state)
+}
+
+fn parse_limit(words: &[&str]) -> Result<SearchLimit, String> {
+    match words {
+        [] => Ok(SearchLimit::Playouts(DEFAULT_PLAYOUTS)),
+        ["playouts", n] => n
+            .parse()
+            .map(SearchLimit::Playouts)
+            .map_err(|_| format!("invalid playouts: {}", n)),
+        ["movetime", ms] => ms
+            .parse()
+            .map(|ms| SearchLimit::Time(Duration::from_millis(ms)))
+            .map_err(|_| format!("invalid movetime: {}", ms)),
+        _ => Err(String::from("go takes `playouts N` or `movetime MS`")),
+    }
+}
+
+struct Engine<W: Write + Send + 'static> {
+    agent: Agent,
+    state: State,
+    output: Arc<Mutex<W>>,
+    stop: Arc<AtomicBool>,
+    search: Option<JoinHandle<()>>,
+}
+
+impl<W: Write + Send + 'static> Engine<W> {
+    fn send(&self, line: &str) {
+        let mut output = self.output.lock().unwrap();
+        let _ = writeln!(output, "{}", line);
+        let _ = output.flush();
+    }
+
+    fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.join().unwrap();
+        }
+    }
+
+    fn go(&mut self, limit: SearchLimit) {
+        if self.state.is_done() {
+            self.send("info string the game is over");
+            self.send("bestmove none");
+            return;
+        }
+        self.stop.store(false, Ordering::Relaxed);
+        let agent = self.agent;
+        let state = self.state;
+        let stop = Arc::clone(&self.stop);
+        let output = Arc::clone(&self.output);
+        self.search = Some(thread::spawn(move || {
+            let action = state.normalize_action(search(agent, &state, &limit, &stop, &output));
+            let mut output = output.lock().unwrap();
+            let _ = writeln!(output, "bestmove {}", format_action(action));
+            let _ = output.flush();
+        }));
+    }
+
+    // Returns false on `quit`.
+    fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            return true;
+        };
+        if command == "isready" {
+            self.send("readyok");
+            return true;
+        }
+        if command == "stop" || command == "quit" {
+            self.stop.store(true, Ordering::Relaxed);
+            self.wait();
+            return command == "stop";
+        }
+        // Every other command waits for the current search so the position stays consistent.
+        self.wait();
+        match command {
+            "newgame" => self.state = State::new(),
+            "position" => match parse_position(args) {
+                Ok(state) => self.state = state,
+                Err(e) => self.send(&format!("info string {}", e)),
+            },
+            "go" => match parse_limit(args) {
+                Ok(limit) => self.go(limit),
+                Err(e) => self.send(&format!("info string {}", e)),
+            },
+            _ => self.send(&format!("info string unknown command: {}", command)),
+        }
+        true
+    }
+}
+
+// Reads commands until `quit` or the end of the input, then waits for a running search and
+// returns the output.
+pub fn run<R: BufRead, W: Write + Send + 'static>(
+    agent: Agent,
+    input: R,
+    output: W,
+) -> io::Result<W> {
+    let mut engine = Engine {
+        agent,
+        state: State::new(),
+        output: Arc::new(Mutex::new(output)),
+        stop: Arc::new(AtomicBool::new(false)),
+        search: None,
+    };
+    for line in input.lines() {
+        if !engine.handle(&line?) {
+            break;
+        }
+    }
+    engine.wait();
+    let output = engine.output;
+    Ok(Arc::try_unwrap(output)
+        .ok()
+        .expect("the search thread has finished")
+        .into_inner()
+        .unwrap())
+}
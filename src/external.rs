@@ -0,0 +1,156 @@
+//! A player backed by an external executable that speaks the engine protocol of [`engine`](crate::engine). Each
+//! action is requested with `position` and `go movetime`, and the process has to answer with
+//! `bestmove` within the time limit plus `TIME_MARGIN`. A crash, a timeout or an unreadable answer
+//! forfeits the game; a crashed process is started again for the next game.
+
+use crate::play::{Decision, Player};
+use crate::quarto::{parse_action, State};
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TIME_MARGIN: Duration = Duration::from_millis(100);
+
+pub struct ExternalPlayer {
+    program: String,
+    args: Vec<String>,
+    time_limit: Duration,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    // `bestmove` answers still owed for searches that ran out of time.
+    late_answers: usize,
+    crashed: bool,
+}
+
+impl ExternalPlayer {
+    // `command` is split on whitespace into the program and its arguments.
+    pub fn spawn(command: &str, time_limit: Duration) -> io::Result<Self> {
+        let mut words = command.split_whitespace().map(String::from);
+        let program = words
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+        let args: Vec<String> = words.collect();
+        let (child, stdin, lines) = Self::start(&program, &args)?;
+        Ok(ExternalPlayer {
+            program,
+            args,
+            time_limit,
+            child,
+            stdin,
+            lines,
+            late_answers: 0,
+            crashed: false,
+        })
+    }
+
+    fn start(program: &str, args: &[String]) -> io::Result<(Child, ChildStdin, Receiver<String>)> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        // Reading on a separate thread lets `decide` wait with a timeout. The channel closes when
+        // the process exits.
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok((child, stdin, lines))
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", line)?;
+        self.stdin.flush()
+    }
+
+    fn request(&mut self, state: &State) -> Result<String, String> {
+        let millis = self.time_limit.as_millis();
+        self.send(&format!("position {}", state.notation()))
+            .and_then(|_| self.send(&format!("go movetime {}", millis)))
+            .map_err(|_| self.crash())?;
+
+        let deadline = Instant::now() + self.time_limit + TIME_MARGIN;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(timeout) {
+                Ok(line) => {
+                    let Some(answer) = line.strip_prefix("bestmove ") else {
+                        continue;
+                    };
+                    if self.late_answers > 0 {
+                        self.late_answers -= 1;
+                        continue;
+                    }
+                    return Ok(answer.to_string());
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    let _ = self.send("stop");
+                    self.late_answers += 1;
+                    return Err(format!("no answer within {} ms", millis));
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(self.crash()),
+            }
+        }
+    }
+
+    fn crash(&mut self) -> String {
+        self.crashed = true;
+        match self.child.try_wait() {
+            Ok(Some(status)) => format!("the engine exited ({})", status),
+            _ => String::from("the engine closed its output"),
+        }
+    }
+
+    fn stop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Player for ExternalPlayer {
+    fn decide(&mut self, state: &State) -> Decision {
+        if self.crashed {
+            return Decision::Forfeit(String::from("the engine is not running"));
+        }
+        match self.request(state) {
+            Ok(answer) => match parse_action(&answer) {
+                Some(action) => Decision::Act(action),
+                None => Decision::Forfeit(format!("unreadable answer: bestmove {}", answer)),
+            },
+            Err(reason) => Decision::Forfeit(reason),
+        }
+    }
+
+    fn new_game(&mut self) {
+        if self.crashed {
+            self.stop();
+            if let Ok((child, stdin, lines)) = Self::start(&self.program, &self.args) {
+                self.child = child;
+                self.stdin = stdin;
+                self.lines = lines;
+                self.late_answers = 0;
+                self.crashed = false;
+            }
+            return;
+        }
+        let _ = self.send("newgame");
+    }
+}
+
+impl Drop for ExternalPlayer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
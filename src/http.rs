@@ -0,0 +1,535 @@
+//! A small HTTP/1.1 server with a JSON API. Every connection carries one request.
+//!
+//! ```text
+//! POST /games              {"engine": "1p"|"2p", "playouts": N}, both optional; the engine
+//!                          plays the given seat with `mcts_action`
+//! GET  /games/{id}         the game, see `game_json`
+//! POST /games/{id}/actions {"place": "b3"|null, "piece": "WCSH"|null}; answers with the game
+//!                          after the action and any engine reply
+//! POST /analyze            {"position": <notation>, "playouts": N}; MCTS statistics of every
+//!                          root action, most visited first
+//! ```
+//!
+//! Errors are answered with a status code and `{"error": "<message>"}`. Actions for the seat of the
+//! engine are refused, also while it searches, which it does without blocking the other requests.
+
+use crate::montecarlo::{mcts_action, mcts_search};
+use crate::play::PLAYER_NAMES;
+use crate::quarto::{format_action, format_cell, parse_cell, Action, Piece, State};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::iter::Peekable;
+use std::net::{TcpListener, TcpStream};
+use std::str::Chars;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How long `serve` waits on a connection for a request or for the client to take the response.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+const DEFAULT_PLAYOUTS: usize = 10000;
+
+const MAX_PLAYOUTS: usize = 1_000_000;
+
+const MAX_BODY: usize = 1 << 16;
+
+#[derive(Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<(), String> {
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        Some(c) => Err(format!("expected `{}`, found `{}`", expected, c)),
+        None => Err(format!("expected `{}`, found the end", expected)),
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    expect(chars, '"')?;
+    let mut string = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(string),
+            '\\' => match chars.next().ok_or("unterminated string")? {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                'r' => string.push('\r'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16).map_err(|_| "invalid escape")?;
+                    string.push(char::from_u32(code).ok_or("invalid escape")?);
+                }
+                c => string.push(c),
+            },
+            c => string.push(c),
+        }
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    match chars.peek() {
+        Some('"') => Ok(Json::String(parse_string(chars)?)),
+        Some(c) if c.is_ascii_alphabetic() => {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphabetic()) {
+                word.push(c);
+                chars.next();
+            }
+            match word.as_str() {
+                "null" => Ok(Json::Null),
+                "true" => Ok(Json::Bool(true)),
+                "false" => Ok(Json::Bool(false)),
+                _ => Err(format!("unexpected word `{}`", word)),
+            }
+        }
+        _ => {
+            let mut number = String::new();
+            while let Some(&c) = chars
+                .peek()
+                .filter(|c| c.is_ascii_digit() || "+-.eE".contains(**c))
+            {
+                number.push(c);
+                chars.next();
+            }
+            number
+                .parse()
+                .map(Json::Number)
+                .map_err(|_| String::from("expected a string, number, boolean or null"))
+        }
+    }
+}
+
+// Only flat objects are needed by the API, so nested objects and arrays are rejected.
+fn parse_object(text: &str) -> Result<HashMap<String, Json>, String> {
+    let mut object = HashMap::new();
+    if text.trim().is_empty() {
+        return Ok(object);
+    }
+    let mut chars = text.trim().chars().peekable();
+    expect(&mut chars, '{')?;
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            skip_whitespace(&mut chars);
+            let key = parse_string(&mut chars)?;
+            skip_whitespace(&mut chars);
+            expect(&mut chars, ':')?;
+            skip_whitespace(&mut chars);
+            object.insert(key, parse_value(&mut chars)?);
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err(String::from("expected `,` or `}`")),
+            }
+        }
+    }
+    if chars.next().is_some() {
+        return Err(String::from("unexpected text after the object"));
+    }
+    Ok(object)
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_option(value: Option<String>) -> String {
+    value.map_or(String::from("null"), |value| json_string(&value))
+}
+
+fn action_json((place, piece): Action) -> String {
+    format!(
+        "\"place\":{},\"piece\":{}",
+        json_option(place.map(format_cell)),
+        json_option(piece.map(|piece| piece.to_string()))
+    )
+}
+
+fn error_json(message: &str) -> String {
+    format!("{{\"error\":{}}}", json_string(message))
+}
+
+fn get_string(object: &HashMap<String, Json>, key: &str) -> Result<Option<String>, String> {
+    match object.get(key) {
+        None | Some(Json::Null) => Ok(None),
+        Some(Json::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(format!("`{}` must be a string", key)),
+    }
+}
+
+fn get_playouts(object: &HashMap<String, Json>) -> Result<usize, String> {
+    match object.get("playouts") {
+        None | Some(Json::Null) => Ok(DEFAULT_PLAYOUTS),
+        Some(&Json::Number(n)) if n >= 1.0 && n <= MAX_PLAYOUTS as f64 && n.fract() == 0.0 => {
+            Ok(n as usize)
+        }
+        Some(_) => Err(format!(
+            "`playouts` must be an integer from 1 to {}",
+            MAX_PLAYOUTS
+        )),
+    }
+}
+
+struct Game {
+    state: State,
+    actions: Vec<Action>,
+    engine: Option<(usize, usize)>,
+}
+
+impl Game {
+    fn apply(&mut self, action: Action) {
+        self.actions.push(action);
+        self.state.apply_action(action);
+    }
+
+    // The playouts of the engine when it is to move.
+    fn engine_to_move(&self) -> Option<usize> {
+        let (seat, playout_number) = self.engine?;
+        (!self.state.is_done() && self.actions.len() % 2 == seat).then_some(playout_number)
+    }
+}
+
+fn game_json(id: u64, game: &Game) -> String {
+    let state = &game.state;
+    let (status, winner) = if !state.is_done() {
+        ("playing", None)
+    } else if state.can_win() {
+        ("won", Some(PLAYER_NAMES[(game.actions.len() - 1) % 2]))
+    } else {
+        ("draw", None)
+    };
+    let board: Vec<String> = (0..State::SIZE)
+        .map(|h| {
+            let cells: Vec<String> = (0..State::SIZE)
+                .map(|w| json_option(state.get_piece(h, w).map(|piece| piece.to_string())))
+                .collect();
+            format!("[{}]", cells.join(","))
+        })
+        .collect();
+    let actions: Vec<String> = game
+        .actions
+        .iter()
+        .map(|&action| json_string(&format_action(action)))
+        .collect();
+    let legal_actions: Vec<String> = state
+        .legal_actions()
+        .into_iter()
+        .map(|action| format!("{{{}}}", action_json(action)))
+        .collect();
+    format!(
+        "{{\"id\":{},\"position\":{},\"turn\":{},\"status\":\"{}\",\"to_move\":{},\"winner\":{},\
+         \"piece_in_hand\":{},\"board\":[{}],\"actions\":[{}],\"legal_actions\":[{}]}}",
+        id,
+        json_string(&state.notation()),
+        state.turn(),
+        status,
+        json_option((!state.is_done()).then(|| PLAYER_NAMES[game.actions.len() % 2].to_string())),
+        json_option(winner.map(String::from)),
+        json_option(state.selected_piece().map(|piece| piece.to_string())),
+        board.join(","),
+        actions.join(","),
+        legal_actions.join(",")
+    )
+}
+
+fn analyze_json(object: &HashMap<String, Json>) -> Result<String, String> {
+    let notation = get_string(object, "position")?.ok_or("`position` is required")?;
+    let state = State::from_notation(&notation).ok_or("invalid position")?;
+    if state.is_done() {
+        return Err(String::from("the game is over"));
+    }
+    let playout_number = get_playouts(object)?;
+
+    // MCTS keeps a child for every piece after a winning placement; they are one action.
+    let mut merged: Vec<(Action, i32, f64)> = Vec::new();
+    for statistics in mcts_search(&state, playout_number) {
+        let action = state.normalize_action(statistics.action);
+        let weighted = statistics.value * statistics.trials as f64;
+        match merged.iter_mut().find(|(a, _, _)| *a == action) {
+            Some((_, trials, value)) => {
+                *trials += statistics.trials;
+                *value += weighted;
+            }
+            None => merged.push((action, statistics.trials, weighted)),
+        }
+    }
+    merged.sort_by_key(|&(_, trials, _)| std::cmp::Reverse(trials));
+    let actions: Vec<String> = merged
+        .iter()
+        .map(|&(action, trials, weighted)| {
+            let value = if trials > 0 {
+                weighted / trials as f64
+            } else {
+                0.0
+            };
+            format!(
+                "{{{},\"visits\":{},\"value\":{:.4}}}",
+                action_json(action),
+                trials,
+                value
+            )
+        })
+        .collect();
+    Ok(format!(
+        "{{\"position\":{},\"playouts\":{},\"actions\":[{}]}}",
+        json_string(&state.notation()),
+        playout_number,
+        actions.join(",")
+    ))
+}
+
+fn parse_action_object(object: &HashMap<String, Json>) -> Result<Action, String> {
+    let place = match get_string(object, "place")? {
+        Some(cell) => Some(parse_cell(&cell).ok_or(format!("invalid cell: {}", cell))?),
+        None => None,
+    };
+    let piece = match get_string(object, "piece")? {
+        Some(piece) => Some(
+            piece
+                .parse::<Piece>()
+                .map_err(|_| format!("invalid piece: {}", piece))?,
+        ),
+        None => None,
+    };
+    Ok((place, piece))
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: String,
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|_| "failed to read the request")?;
+    let mut words = line.split_whitespace();
+    let (Some(method), Some(path)) = (words.next(), words.next()) else {
+        return Err(String::from("malformed request line"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        reader
+            .read_line(&mut line)
+            .map_err(|_| "failed to read the headers")?;
+        let header = line.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_| "invalid Content-Length")?;
+            }
+        }
+    }
+    if content_length > MAX_BODY {
+        return Err(String::from("the body is too large"));
+    }
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|_| "failed to read the body")?;
+    let body = String::from_utf8(body).map_err(|_| "the body is not UTF-8")?;
+    Ok(Request { method, path, body })
+}
+
+fn write_response(mut stream: &TcpStream, status: u16, body: &str) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Unprocessable Entity",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[derive(Default)]
+struct Games {
+    next_id: u64,
+    games: HashMap<u64, Game>,
+}
+
+fn route(games: &Mutex<Games>, request: &Request) -> (u16, String) {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let object = match parse_object(&request.body) {
+        Ok(object) => object,
+        Err(e) => return (400, error_json(&format!("invalid JSON: {}", e))),
+    };
+    let result = match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["games"]) => create_game(games, &object).map(|body| (201, body)),
+        ("GET", ["games", id]) => parse_id(id)
+            .and_then(|id| with_game(games, id, |game| Ok(game_json(id, game))))
+            .map(|body| (200, body)),
+        ("POST", ["games", id, "actions"]) => parse_id(id)
+            .and_then(|id| play_action(games, id, &object))
+            .map(|body| (200, body)),
+        ("POST", ["analyze"]) => analyze_json(&object).map(|body| (200, body)),
+        (_, ["games"]) | (_, ["games", _]) | (_, ["games", _, "actions"]) | (_, ["analyze"]) => {
+            return (405, error_json("method not allowed"))
+        }
+        _ => return (404, error_json("not found")),
+    };
+    match result {
+        Ok(response) => response,
+        Err(e) if e == "no such game" => (404, error_json(&e)),
+        Err(e) => (400, error_json(&e)),
+    }
+}
+
+fn create_game(games: &Mutex<Games>, object: &HashMap<String, Json>) -> Result<String, String> {
+    let engine = match get_string(object, "engine")? {
+        Some(seat) => Some((
+            PLAYER_NAMES
+                .iter()
+                .position(|&name| name == seat)
+                .ok_or("`engine` must be \"1p\" or \"2p\"")?,
+            get_playouts(object)?,
+        )),
+        None => None,
+    };
+    let game = Game {
+        state: State::new(),
+        actions: Vec::new(),
+        engine,
+    };
+    let id = {
+        let mut games = games.lock().unwrap();
+        games.next_id += 1;
+        let id = games.next_id;
+        games.games.insert(id, game);
+        id
+    };
+    play_engine(games, id);
+    with_game(games, id, |game| Ok(game_json(id, game)))
+}
+
+fn play_action(
+    games: &Mutex<Games>,
+    id: u64,
+    object: &HashMap<String, Json>,
+) -> Result<String, String> {
+    with_game(games, id, |game| {
+        if game.engine_to_move().is_some() {
+            return Err(String::from("the engine is to move"));
+        }
+        let action = game.state.normalize_action(parse_action_object(object)?);
+        if !game.state.is_legal_action(action) {
+            return Err(format!("illegal action: {}", format_action(action)));
+        }
+        game.apply(action);
+        Ok(())
+    })?;
+    play_engine(games, id);
+    with_game(games, id, |game| Ok(game_json(id, game)))
+}
+
+// Searches on a copy of the state with the games unlocked, so that a long search does not hold up
+// the other requests. Only the searching request can move for the engine, so the game has not
+// moved on when the lock is taken again.
+fn play_engine(games: &Mutex<Games>, id: u64) {
+    while let Ok(Some((state, playout_number))) = with_game(games, id, |game| {
+        Ok(game.engine_to_move().map(|n| (game.state, n)))
+    }) {
+        let action = state.normalize_action(mcts_action(&state, playout_number));
+        let _ = with_game(games, id, |game| {
+            game.apply(action);
+            Ok(())
+        });
+    }
+}
+
+fn parse_id(id: &str) -> Result<u64, String> {
+    id.parse().map_err(|_| String::from("no such game"))
+}
+
+fn with_game<T>(
+    games: &Mutex<Games>,
+    id: u64,
+    f: impl FnOnce(&mut Game) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut games = games.lock().unwrap();
+    let game = games.games.get_mut(&id).ok_or("no such game")?;
+    f(game)
+}
+
+pub fn serve(listener: &TcpListener) -> io::Result<()> {
+    serve_with_timeout(listener, TIMEOUT)
+}
+
+pub fn serve_with_timeout(listener: &TcpListener, timeout: Duration) -> io::Result<()> {
+    let games = Arc::new(Mutex::new(Games::default()));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        let games = Arc::clone(&games);
+        thread::spawn(move || {
+            let (status, body) = match read_request(&stream) {
+                Ok(request) => route(&games, &request),
+                Err(e) => (400, error_json(&e)),
+            };
+            let _ = write_response(&stream, status, &body);
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_objects() {
+        let object =
+            parse_object(" {\"a\": \"x\\\"y\", \"b\": -1.5e1, \"c\": null, \"d\": true} ").unwrap();
+        assert!(object["a"] == Json::String(String::from("x\"y")));
+        assert!(object["b"] == Json::Number(-15.0));
+        assert!(object["c"] == Json::Null);
+        assert!(object["d"] == Json::Bool(true));
+        assert!(parse_object("{\"a\": [1]}").is_err());
+        assert!(parse_object("{\"a\": 1} x").is_err());
+    }
+}
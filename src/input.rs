@@ -0,0 +1,243 @@
+//! The commands of the text interface: moves written as cells and pieces, with pieces given as
+//! codes or as any words that single out one unused piece, and the other commands of [`HELP`].
+
+use crate::quarto::{parse_cell, Piece, State};
+use std::fmt;
+use std::fmt::Formatter;
+
+pub enum Command {
+    Move {
+        place: Option<(usize, usize)>,
+        piece: Option<Piece>,
+    },
+    Quarto,
+    Undo,
+    Hint,
+    Save(Option<String>),
+    Resign,
+    Board,
+    Help,
+}
+
+pub enum ParseError {
+    Empty,
+    UnknownWord(String),
+    NoMatchingPiece,
+    AmbiguousPiece(Vec<Piece>),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "empty input"),
+            ParseError::UnknownWord(word) => write!(f, "unknown word: {}", word),
+            ParseError::NoMatchingPiece => write!(f, "no unused piece matches"),
+            ParseError::AmbiguousPiece(pieces) => {
+                write!(f, "ambiguous piece, candidates:")?;
+                for piece in pieces {
+                    write!(f, " {}", piece)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+pub const HELP: &str = "\
+cells:    a1 .. d4 (column letter, row number from 1) or `h w` with 0-based numbers
+pieces:   a code such as BSTF, or words such as `white circle short hole`;
+          any subset of the words works when only one unused piece matches
+          (black/dark, white/light, square, circle/round, tall/high, short/low, flat/solid, hole/hollow)
+moves:    a cell, a piece, or both at once such as `b3 white tall`
+quarto:   under the call rule, `quarto` after placing calls the win, and before placing
+          claims a line the opponent left open
+commands: undo, hint, save [path], resign, board, help";
+
+// (attribute index in `Piece::get_idx`, value) for each attribute word.
+fn attribute_word(word: &str) -> Option<(usize, usize)> {
+    match word {
+        "black" | "dark" => Some((0, 0)),
+        "white" | "light" => Some((0, 1)),
+        "square" => Some((1, 0)),
+        "circle" | "round" => Some((1, 1)),
+        "tall" | "high" => Some((2, 0)),
+        "short" | "low" => Some((2, 1)),
+        "flat" | "solid" => Some((3, 0)),
+        "hole" | "hollow" => Some((3, 1)),
+        _ => None,
+    }
+}
+
+fn attribute(piece: &Piece, idx: usize) -> usize {
+    let (color, shape, height, top) = piece.get_idx();
+    [color, shape, height, top][idx]
+}
+
+pub fn resolve_piece(words: &[&str], candidates: &[Piece]) -> Result<Piece, ParseError> {
+    let mut constraints = Vec::new();
+    for word in words {
+        if let Ok(piece) = word.to_uppercase().parse::<Piece>() {
+            constraints.extend((0..4).map(|idx| (idx, attribute(&piece, idx))));
+            continue;
+        }
+        match attribute_word(&word.to_lowercase()) {
+            Some(constraint) => constraints.push(constraint),
+            None => return Err(ParseError::UnknownWord(word.to_string())),
+        }
+    }
+    let matches: Vec<Piece> = candidates
+        .iter()
+        .filter(|piece| {
+            constraints
+                .iter()
+                .all(|&(idx, value)| attribute(piece, idx) == value)
+        })
+        .copied()
+        .collect();
+    match matches.len() {
+        0 => Err(ParseError::NoMatchingPiece),
+        1 => Ok(matches[0]),
+        _ => Err(ParseError::AmbiguousPiece(matches)),
+    }
+}
+
+pub fn parse_command(input: &str, state: &State) -> Result<Command, ParseError> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    let first = match words.first() {
+        Some(word) => word.to_lowercase(),
+        None => return Err(ParseError::Empty),
+    };
+    match first.as_str() {
+        "quarto" | "claim" => return Ok(Command::Quarto),
+        "undo" => return Ok(Command::Undo),
+        "hint" => return Ok(Command::Hint),
+        "save" => return Ok(Command::Save(words.get(1).map(|path| path.to_string()))),
+        "resign" => return Ok(Command::Resign),
+        "board" => return Ok(Command::Board),
+        "help" | "?" => return Ok(Command::Help),
+        _ => {}
+    }
+
+    // Cells off the board are left to be reported as unknown words.
+    let on_board = |&(h, w): &(usize, usize)| h < State::SIZE && w < State::SIZE;
+    let (place, rest) = if let Some(cell) = parse_cell(words[0]).filter(on_board) {
+        (Some(cell), &words[1..])
+    } else if let (Some(Ok(h)), Some(Ok(w))) = (
+        words.first().map(|word| word.parse::<usize>()),
+        words.get(1).map(|word| word.parse::<usize>()),
+    ) {
+        if !on_board(&(h, w)) {
+            return Err(ParseError::UnknownWord(format!("{} {}", h, w)));
+        }
+        (Some((h, w)), &words[2..])
+    } else {
+        (None, &words[..])
+    };
+    let piece = if rest.is_empty() {
+        None
+    } else {
+        Some(resolve_piece(rest, &state.legal_pieces())?)
+    };
+    Ok(Command::Move { place, piece })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> State {
+        State::from_notation("BSTF.../..../..../.... WCSH").unwrap()
+    }
+
+    fn piece(code: &str) -> Piece {
+        code.parse().unwrap()
+    }
+
+    fn parse_move(input: &str) -> (Option<(usize, usize)>, Option<Piece>) {
+        match parse_command(input, &state()) {
+            Ok(Command::Move { place, piece }) => (place, piece),
+            Ok(_) => panic!("not a move: {}", input),
+            Err(e) => panic!("{}: {}", input, e),
+        }
+    }
+
+    fn parse_error(input: &str) -> ParseError {
+        match parse_command(input, &state()) {
+            Ok(_) => panic!("parsed: {}", input),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn moves_are_parsed() {
+        assert_eq!(parse_move("b3"), (Some((2, 1)), None));
+        assert_eq!(parse_move("D1"), (Some((0, 3)), None));
+        assert_eq!(parse_move("1 2"), (Some((1, 2)), None));
+        assert_eq!(parse_move("bsth"), (None, Some(piece("BSTH"))));
+        assert_eq!(
+            parse_move("b3 white tall flat square"),
+            (Some((2, 1)), Some(piece("WSTF")))
+        );
+        assert_eq!(parse_move("1 2 BCTH"), (Some((1, 2)), Some(piece("BCTH"))));
+        // WCSH is in hand, so WCSF is the only unused piece left.
+        assert_eq!(parse_move("light round low"), (None, Some(piece("WCSF"))));
+    }
+
+    #[test]
+    fn partial_pieces_must_be_unambiguous() {
+        match parse_error("white circle") {
+            ParseError::AmbiguousPiece(pieces) => {
+                let mut codes: Vec<String> = pieces.iter().map(Piece::to_string).collect();
+                codes.sort();
+                assert_eq!(codes, ["WCSF", "WCTF", "WCTH"]);
+            }
+            e => panic!("{}", e),
+        }
+        let candidates = state().legal_pieces();
+        assert_eq!(
+            resolve_piece(&["white", "circle", "tall", "hole"], &candidates).ok(),
+            Some(piece("WCTH"))
+        );
+        assert!(matches!(
+            resolve_piece(&["white", "circle", "short", "hole"], &candidates),
+            Err(ParseError::NoMatchingPiece)
+        ));
+        assert!(matches!(
+            resolve_piece(&["black", "white"], &candidates),
+            Err(ParseError::NoMatchingPiece)
+        ));
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert!(matches!(parse_error(""), ParseError::Empty));
+        assert!(matches!(parse_error("   \n"), ParseError::Empty));
+        assert!(matches!(parse_error("bstf"), ParseError::NoMatchingPiece));
+        match parse_error("b3 purple") {
+            ParseError::UnknownWord(word) => assert_eq!(word, "purple"),
+            e => panic!("{}", e),
+        }
+        assert!(matches!(parse_error("z9"), ParseError::UnknownWord(_)));
+        assert!(matches!(parse_error("1 x"), ParseError::UnknownWord(_)));
+        match parse_error("4 0") {
+            ParseError::UnknownWord(word) => assert_eq!(word, "4 0"),
+            e => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn commands_are_parsed() {
+        let parse = |input: &str| parse_command(input, &state()).ok().unwrap();
+        assert!(matches!(parse("undo"), Command::Undo));
+        assert!(matches!(parse("Hint"), Command::Hint));
+        assert!(matches!(parse("resign"), Command::Resign));
+        assert!(matches!(parse("board"), Command::Board));
+        assert!(matches!(parse("?"), Command::Help));
+        assert!(matches!(parse("claim"), Command::Quarto));
+        assert!(matches!(parse("save"), Command::Save(None)));
+        match parse("save games/a.txt") {
+            Command::Save(Some(path)) => assert_eq!(path, "games/a.txt"),
+            _ => panic!("not a save"),
+        }
+    }
+}
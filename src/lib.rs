@@ -0,0 +1,53 @@
+//! Quarto rules, engines and tooling.
+//!
+//! - [`quarto`]: the board, pieces, actions and rules.
+//! - [`agents`]: functions that pick an action for a position, from random play to MCTS.
+//! - [`montecarlo`]: Monte Carlo searches with their statistics, including a PUCT search guided by
+//!   the neural network in [`nn`].
+//! - [`match`](match/index.html): playing agents against each other and collecting outcomes.
+//!
+//! The other modules build on these: game records and interactive play ([`play`]), exact
+//! solving ([`solver`], [`tablebase`]), move generation counts ([`perft`]), benchmarks
+//! ([`bench`](mod@bench)), self-play training data ([`selfplay`]) and the training on it
+//! ([`train`]), the opening book, post-game analysis and the network, HTTP and engine protocols
+//! used by the `quarto` binary. [`rng`] seeds the randomness of all of them.
+//!
+//! ```
+//! use quarto::agents::random_action;
+//! use quarto::montecarlo::mcts_action;
+//! use quarto::quarto::State;
+//!
+//! let mut state = State::new();
+//! while !state.is_done() {
+//!     let action = if state.turn() % 2 == 0 {
+//!         mcts_action(&state, 100)
+//!     } else {
+//!         random_action(&state)
+//!     };
+//!     state.apply_action(action);
+//! }
+//! ```
+
+pub mod agents;
+pub mod analysis;
+pub mod bench;
+mod coach;
+pub mod engine;
+pub mod external;
+pub mod http;
+mod input;
+pub mod r#match;
+pub mod montecarlo;
+pub mod net;
+pub mod nn;
+pub mod opening_book;
+pub mod perft;
+pub mod play;
+pub mod quarto;
+mod render;
+pub mod rng;
+pub mod selfplay;
+pub mod solver;
+pub mod tablebase;
+pub mod train;
+pub mod tui;
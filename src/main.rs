@@ -1,36 +1,329 @@
-use crate::montecarlo::{mcts_action, primitive_monte_carlo_action};
-use crate::play::{human_action, play_game, test_first_player_win_rate};
-use crate::quarto::{Piece, State};
-use crate::random::random_action;
+use quarto::agents::random_action;
+use quarto::analysis::Analysis;
+use quarto::bench;
+use quarto::engine::{self, Agent};
+use quarto::external::ExternalPlayer;
+use quarto::montecarlo::{
+    mcts_action, mcts_rave_action, primitive_monte_carlo_action, RAVE_EQUIVALENCE,
+};
+use quarto::nn::{Network, NetworkPlayer};
+use quarto::opening_book::{self, OpeningBook};
+use quarto::play::{
+    play_game, play_game_with_rules, replay, ActionFn, GameRecord, HumanPlayer, Player,
+};
+use quarto::quarto::{Piece, RuleSet, State};
+use quarto::r#match::{test_first_player_win_rate, GameOutcome};
+use quarto::tablebase::{self, Tablebase};
+use quarto::train::{self, TrainConfig, GATE_SCORE};
+use quarto::{http, net, selfplay, tui};
+use std::env;
+use std::io;
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
 
-mod montecarlo;
-mod play;
-mod quarto;
-mod random;
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == name)?;
+    let value = args
+        .get(i + 1)
+        .unwrap_or_else(|| panic!("{} requires a value", name))
+        .clone();
+    args.drain(i..i + 2);
+    Some(value)
+}
+
+fn save_record(record: &GameRecord, path: &Option<String>) {
+    if let Some(path) = path {
+        record.save(path).expect("failed to write the game record");
+    }
+}
+
+// An agent name plays in process with 1000 playouts, `puct:<weights>` plays a PUCT search with
+// 1000 network evaluations, and anything else is run as an external engine.
+fn match_player(spec: &str, time_limit: Duration) -> Box<dyn Player> {
+    if let Some(path) = spec.strip_prefix("puct:") {
+        return Box::new(NetworkPlayer {
+            network: Network::load(path).expect("failed to load the network weights"),
+            playout_number: 1000,
+        });
+    }
+    let action_fn: ActionFn = match spec {
+        "mcts" => {
+            |state: &State| -> (Option<(usize, usize)>, Option<Piece>) { mcts_action(state, 1000) }
+        }
+        "rave" => |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
+            mcts_rave_action(state, 1000, RAVE_EQUIVALENCE)
+        },
+        "primitive" => |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
+            primitive_monte_carlo_action(state, 1000)
+        },
+        "random" => random_action,
+        _ => {
+            return Box::new(
+                ExternalPlayer::spawn(spec, time_limit).expect("failed to start the engine"),
+            )
+        }
+    };
+    Box::new(action_fn)
+}
 
 fn main() {
-    // play_game(random_action, random_action);
-    // play_game(human_action, random_action);
-    play_game(
-        |state: &State| -> (Option<(usize, usize)>, Option<Piece>) { mcts_action(state, 1000) },
-        |state: &State| -> (Option<(usize, usize)>, Option<Piece>) { mcts_action(state, 10000) },
-    );
-    // test_first_player_win_rate(
-    //     100,
-    //     (
-    //         (
-    //             "primitiveMonteCarloAction 1000",
-    //             |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
-    //                 primitive_monte_carlo_action(state, 1000)
-    //             },
-    //         ),
-    //         (
-    //             "mctsAction 1000",
-    //             |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
-    //                 mcts_action(state, 1000)
-    //             },
-    //         ),
-    //         // ("randomAI", random_action),
-    //     ),
-    // )
+    let mut args: Vec<String> = env::args().collect();
+    if let Some(path) = take_option(&mut args, "--book") {
+        opening_book::load(&path).expect("failed to load the opening book");
+    }
+    if let Some(path) = take_option(&mut args, "--tablebase") {
+        tablebase::load(&path).expect("failed to load the tablebase");
+    }
+    let record_path = take_option(&mut args, "--record");
+    // Applies to the locally played games: `random`, `human-random` and `human`.
+    let rules: RuleSet = take_option(&mut args, "--rules")
+        .map_or_else(RuleSet::default, |spec| spec.parse().unwrap());
+    // Options of `bench`: where to save the results, the results to compare against and the least
+    // time each benchmark runs for in milliseconds.
+    let bench_save = take_option(&mut args, "--save");
+    let bench_baseline = take_option(&mut args, "--baseline");
+    let bench_time = take_option(&mut args, "--time").map_or(1000, |arg| arg.parse().unwrap());
+    match args.get(1).map(String::as_str) {
+        Some("book") => {
+            let path = args.get(2).map_or("opening_book.txt", String::as_str);
+            let plies = args.get(3).map_or(3, |arg| arg.parse().unwrap());
+            let playout_number = args.get(4).map_or(100000, |arg| arg.parse().unwrap());
+            let book = OpeningBook::generate(plies, playout_number, &mut |ply, positions| {
+                println!("ply {}: {} positions", ply, positions)
+            });
+            book.save(path).expect("failed to write the opening book");
+            println!("{} positions written to {}", book.len(), path);
+        }
+        Some("tui") => {
+            let human_player = match args.get(2).map(String::as_str) {
+                Some("second") => 1,
+                _ => 0,
+            };
+            tui::run(
+                |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
+                    mcts_action(state, 10000)
+                },
+                human_player,
+            )
+            .expect("terminal error");
+        }
+        Some("selfplay") => {
+            let path = args.get(2).map_or("selfplay.bin", String::as_str);
+            let game_number = args.get(3).map_or(100, |arg| arg.parse().unwrap());
+            let playout_number = args.get(4).map_or(1000, |arg| arg.parse().unwrap());
+            let thread_number = args.get(5).map_or_else(
+                || thread::available_parallelism().map_or(1, |n| n.get()),
+                |arg| arg.parse().unwrap(),
+            );
+            let seed = args.get(6).map_or(0, |arg| arg.parse().unwrap());
+            let samples = selfplay::generate(game_number, playout_number, thread_number, seed);
+            selfplay::save(&samples, path).expect("failed to write the self-play data");
+            println!(
+                "{} positions from {} games written to {}",
+                samples.len(),
+                game_number,
+                path
+            );
+        }
+        Some("train") => {
+            let samples = selfplay::load(&args[2]).expect("failed to read the self-play data");
+            let path = args.get(3).map_or("network.qnet", String::as_str);
+            let config = TrainConfig {
+                epochs: args.get(4).map_or(10, |arg| arg.parse().unwrap()),
+                ..TrainConfig::default()
+            };
+            let hidden = args.get(5).map_or(64, |arg| arg.parse().unwrap());
+            let game_number = args.get(6).map_or(10, |arg| arg.parse().unwrap());
+            let previous = Network::load(path).ok();
+            let network = previous
+                .clone()
+                .unwrap_or_else(|| Network::random(&[hidden]));
+            let (network, epochs) = train::train(network, &samples, &config, Some(path))
+                .expect("failed to write a checkpoint");
+            for (i, epoch) in epochs.iter().enumerate() {
+                println!(
+                    "epoch {}: training {:.4} (policy {:.4}, value {:.4}), validation {:.4} (policy {:.4}, value {:.4})",
+                    i + 1,
+                    epoch.training.total(),
+                    epoch.training.policy,
+                    epoch.training.value,
+                    epoch.validation.total(),
+                    epoch.validation.policy,
+                    epoch.validation.value
+                );
+            }
+            let score = previous.map_or(1.0, |previous| {
+                train::gate(&network, &previous, game_number, 200)
+            });
+            if score >= GATE_SCORE {
+                network
+                    .save(path)
+                    .expect("failed to write the network weights");
+                println!("promoted with a score of {:.3}, written to {}", score, path);
+            } else {
+                println!(
+                    "rejected with a score of {:.3}, {} is unchanged",
+                    score, path
+                );
+            }
+        }
+        Some("tablebase") => {
+            // Only the endgames of `root_number` random games are solved, not every position with
+            // at most `empty_limit` empty squares.
+            let path = args.get(2).map_or("tablebase.bin", String::as_str);
+            let empty_limit = args.get(3).map_or(6, |arg| arg.parse().unwrap());
+            let root_number = args.get(4).map_or(100, |arg| arg.parse().unwrap());
+            let tablebase = Tablebase::sample(empty_limit, root_number);
+            tablebase.save(path).expect("failed to write the tablebase");
+            println!(
+                "{} positions from the endgames of {} sampled games written to {}",
+                tablebase.len(),
+                root_number,
+                path
+            );
+        }
+        Some("random") => {
+            let mut random: ActionFn = random_action;
+            let mut opponent: ActionFn = random_action;
+            play_game_with_rules(&mut random, &mut opponent, rules);
+        }
+        Some("human-random") => {
+            let mut human = HumanPlayer { coach: false };
+            let mut random: ActionFn = random_action;
+            play_game_with_rules(&mut human, &mut random, rules);
+        }
+        Some("human") => {
+            let mut engine: ActionFn = |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
+                mcts_action(state, 10000)
+            };
+            let coach = args.get(2).is_some_and(|arg| arg == "coach");
+            let record = play_game_with_rules(&mut HumanPlayer { coach }, &mut engine, rules);
+            save_record(&record, &record_path);
+        }
+        Some("serve") => {
+            let address = args.get(2).map_or("127.0.0.1:7878", String::as_str);
+            let listener = TcpListener::bind(address).expect("failed to listen");
+            println!("waiting for players on {}", address);
+            let (record, _) = net::serve(&listener, &mut io::stdout()).expect("network error");
+            save_record(&record, &record_path);
+        }
+        Some("http") => {
+            let address = args.get(2).map_or("127.0.0.1:8080", String::as_str);
+            let listener = TcpListener::bind(address).expect("failed to listen");
+            println!("serving the HTTP API on {}", address);
+            http::serve(&listener).expect("network error");
+        }
+        Some("connect") => {
+            let address = args.get(2).map_or("127.0.0.1:7878", String::as_str);
+            let mut engine: ActionFn = |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
+                mcts_action(state, 10000)
+            };
+            let mut random: ActionFn = random_action;
+            let mut human = HumanPlayer { coach: false };
+            let (name, player): (&str, &mut dyn Player) = match args.get(3).map(String::as_str) {
+                Some("mcts") => ("mcts", &mut engine),
+                Some("random") => ("random", &mut random),
+                _ => ("human", &mut human),
+            };
+            net::connect(address, name, player, &mut io::stdout()).expect("network error");
+        }
+        Some("engine") => {
+            let name = args.get(2).map_or("mcts", String::as_str);
+            let agent = Agent::from_name(name).expect("unknown agent");
+            engine::run(agent, io::stdin().lock(), io::stdout()).expect("failed to read stdin");
+        }
+        Some("bench") => {
+            let baseline =
+                bench_baseline.map(|path| bench::load(&path).expect("failed to read the baseline"));
+            let measurements = bench::run(
+                args.get(2).map(String::as_str),
+                Duration::from_millis(bench_time),
+            );
+            print!("{}", bench::report(&measurements, baseline.as_deref()));
+            if let Some(path) = bench_save {
+                bench::save(&measurements, &path).expect("failed to write the baseline");
+            }
+        }
+        Some("replay") => {
+            let path = args.get(2).expect("replay requires a game record");
+            let record = GameRecord::load(path).expect("failed to read the game record");
+            print!("{}", replay(&record));
+        }
+        Some("analyze") => {
+            let path = args.get(2).expect("analyze requires a game record");
+            let playout_number = args.get(3).map_or(10000, |arg| arg.parse().unwrap());
+            let record = GameRecord::load(path).expect("failed to read the game record");
+            print!("{}", Analysis::analyze(&record, playout_number));
+        }
+        Some("winrate") => {
+            let mut primitive: ActionFn =
+                |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
+                    primitive_monte_carlo_action(state, 1000)
+                };
+            let mut mcts: ActionFn = |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
+                mcts_action(state, 1000)
+            };
+            test_first_player_win_rate(
+                100,
+                (
+                    ("primitiveMonteCarloAction 1000", &mut primitive),
+                    ("mctsAction 1000", &mut mcts),
+                ),
+            );
+        }
+        Some("winrate-rave") => {
+            let mut rave: ActionFn = |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
+                mcts_rave_action(state, 1000, RAVE_EQUIVALENCE)
+            };
+            let mut mcts: ActionFn = |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
+                mcts_action(state, 1000)
+            };
+            test_first_player_win_rate(
+                100,
+                (
+                    ("mctsRaveAction 1000", &mut rave),
+                    ("mctsAction 1000", &mut mcts),
+                ),
+            );
+        }
+        Some("match") => {
+            let game_number = args.get(4).map_or(10, |arg| arg.parse().unwrap());
+            let time_limit =
+                Duration::from_millis(args.get(5).map_or(1000, |arg| arg.parse().unwrap()));
+            let mut players: Vec<Box<dyn Player>> = args[2..4]
+                .iter()
+                .map(|spec| match_player(spec, time_limit))
+                .collect();
+            let (first, second) = players.split_at_mut(1);
+            let outcomes = test_first_player_win_rate(
+                game_number,
+                (
+                    (args[2].as_str(), first[0].as_mut()),
+                    (args[3].as_str(), second[0].as_mut()),
+                ),
+            );
+            for (i, outcome) in outcomes.iter().enumerate() {
+                if let GameOutcome::Forfeit { player, reason } = outcome {
+                    println!(
+                        "game {}: {} forfeited: {}",
+                        i + 1,
+                        args[2 + (player ^ (i % 2))],
+                        reason
+                    );
+                }
+            }
+        }
+        _ => {
+            let record = play_game(
+                |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
+                    mcts_action(state, 1000)
+                },
+                |state: &State| -> (Option<(usize, usize)>, Option<Piece>) {
+                    mcts_action(state, 10000)
+                },
+            );
+            save_record(&record, &record_path);
+        }
+    }
 }
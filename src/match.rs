@@ -0,0 +1,114 @@
+//! Games between players, with forfeits for illegal actions and failures.
+
+use crate::play::{Decision, GameRecord, Player};
+use crate::quarto::{format_action, State};
+
+/// How a game between two players ended.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameOutcome {
+    Finished { first_player_score: f64 },
+    Forfeit { player: usize, reason: String },
+}
+
+impl GameOutcome {
+    /// A forfeit scores like a loss for the player who forfeited.
+    pub fn first_player_score(&self) -> f64 {
+        match self {
+            GameOutcome::Finished { first_player_score } => *first_player_score,
+            GameOutcome::Forfeit { player, .. } => *player as f64,
+        }
+    }
+}
+
+/// Plays one game without printing. Illegal actions, resignations and failures forfeit the game.
+pub fn play_match_game(mut players: [&mut dyn Player; 2]) -> (GameRecord, GameOutcome) {
+    let mut record = GameRecord::new();
+    let mut state = State::new();
+    for player in players.iter_mut() {
+        player.new_game();
+    }
+    loop {
+        let mover = record.actions.len() % 2;
+        let reason = match players[mover].decide(&state) {
+            Decision::Act(action) => {
+                let action = state.normalize_action(action);
+                if state.is_legal_action(action) {
+                    record.actions.push(action);
+                    state.apply_action(action);
+                    if state.is_done() {
+                        let first_player_score = state.get_first_player_score_for_win_rate();
+                        return (record, GameOutcome::Finished { first_player_score });
+                    }
+                    continue;
+                }
+                format!("illegal action {}", format_action(action))
+            }
+            Decision::Forfeit(reason) => reason,
+            _ => String::from("resigned"),
+        };
+        record.resigned = Some(mover);
+        return (
+            record,
+            GameOutcome::Forfeit {
+                player: mover,
+                reason,
+            },
+        );
+    }
+}
+
+/// Plays each pair of games with the seats swapped and prints the results. Returns the outcomes in
+/// play order; the first player of the pair moves first in even games.
+pub fn test_first_player_win_rate(
+    game_number: i32,
+    ai_pairs: ((&str, &mut dyn Player), (&str, &mut dyn Player)),
+) -> Vec<GameOutcome> {
+    let ((first_name, first_ai), (second_name, second_ai)) = ai_pairs;
+    let names = [first_name, second_name];
+    let mut outcomes = Vec::new();
+    let mut first_player_win_rate = 0.0;
+    for i in 0..game_number {
+        for j in 0..2 {
+            let (record, outcome) = if j == 0 {
+                play_match_game([&mut *first_ai, &mut *second_ai])
+            } else {
+                play_match_game([&mut *second_ai, &mut *first_ai])
+            };
+            // The name of the player in seat `player` of this game.
+            let seat = |player: usize| names[player ^ j];
+            match &outcome {
+                GameOutcome::Finished { first_player_score } if *first_player_score > 0.5 => {
+                    println!("{} win!!", seat(0))
+                }
+                GameOutcome::Finished { first_player_score } if *first_player_score < 0.5 => {
+                    println!("{} win!!", seat(1))
+                }
+                GameOutcome::Finished { .. } => println!("draw"),
+                GameOutcome::Forfeit { player, reason } => {
+                    println!("{} forfeits: {}", seat(*player), reason)
+                }
+            }
+
+            let mut win_rate_point = outcome.first_player_score();
+            if j == 1 {
+                win_rate_point = 1.0 - win_rate_point;
+            }
+            first_player_win_rate += win_rate_point;
+            outcomes.push(outcome);
+
+            record.final_state().print();
+        }
+        println!(
+            "i {}, w {}",
+            i,
+            first_player_win_rate / ((i + 1) * 2) as f64
+        );
+    }
+    first_player_win_rate /= (game_number * 2) as f64;
+    println!(
+        "Winning rate of {} to {}:\t{}",
+        first_name, second_name, first_player_win_rate
+    );
+    outcomes
+}
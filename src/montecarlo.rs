@@ -1,7 +1,15 @@
-use crate::quarto::{Piece, State, WinningStatus};
-use crate::random::random_action;
-use rand::{thread_rng, Rng};
+//! Monte Carlo evaluation: flat playouts, UCT and RAVE tree searches.
+
+use crate::agents::random_action;
+use crate::nn::{action_index, Network, POLICY_SIZE};
+use crate::opening_book::book_move;
+use crate::quarto::{Action, Objective, Piece, State};
+use crate::rng::thread_rng;
+use crate::tablebase::probe;
+use rand::Rng;
 use std::cmp::max;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 fn find_winning_place(state: &State) -> Option<(usize, usize)> {
     for (h, w) in state.legal_placements() {
@@ -12,105 +20,147 @@ fn find_winning_place(state: &State) -> Option<(usize, usize)> {
     None
 }
 
-fn playout(state: &mut State) -> f64 {
-    match state.get_winning_status() {
-        WinningStatus::WIN => 1.0,
-        WinningStatus::DRAW => 0.5,
-        WinningStatus::NONE => {
-            let winning_place = find_winning_place(state);
-            if let Some((h, w)) = winning_place {
-                state.put_piece(h, w);
-                return 1.0;
-            }
+// Completing a line is only worth taking when it does not lose.
+fn completes_lines(state: &State) -> bool {
+    state.rules().objective != Objective::Misere
+}
 
-            let (place, piece) = random_action(state);
-            if let Some(p) = place {
-                state.put_piece(p.0, p.1);
-            }
-            match state.get_winning_status() {
-                WinningStatus::WIN => 1.0,
-                WinningStatus::DRAW => 0.5,
-                WinningStatus::NONE => {
-                    if let Some(piece) = piece {
-                        state.select_piece(piece);
-                    }
-                    1.0 - playout(state)
-                }
-                _ => panic!("unreachable error"),
+pub(crate) fn playout(state: &mut State) -> f64 {
+    playout_with_trace(state, None)
+}
+
+// The reward of the player to move, or of the player who ended the game when `state` is over.
+fn playout_with_trace(state: &mut State, mut trace: Option<&mut Vec<Action>>) -> f64 {
+    if state.is_done() {
+        return state.reward();
+    }
+    let completes = completes_lines(state);
+    if state.can_claim() && completes {
+        state.apply_action((None, None));
+        if let Some(trace) = trace {
+            trace.push((None, None));
+        }
+        return state.reward();
+    }
+    if let Some(value) = probe(state) {
+        return value;
+    }
+
+    let winning_place = find_winning_place(state).filter(|_| completes);
+    if let Some((h, w)) = winning_place {
+        state.put_piece(h, w);
+        if let Some(trace) = trace {
+            trace.push((Some((h, w)), None));
+        }
+        return state.reward();
+    }
+
+    let action = random_action(state);
+    if let Some(trace) = trace.as_deref_mut() {
+        trace.push(action);
+    }
+    state.apply_action(action);
+    if state.is_done() {
+        return state.reward();
+    }
+    1.0 - playout_with_trace(state, trace)
+}
+
+// The result of an action for the player taking it, from one playout of the position it leads to.
+fn action_value(state: &State, action: Action) -> f64 {
+    let mut next_state = *state;
+    next_state.apply_action(action);
+    if next_state.is_done() {
+        next_state.reward()
+    } else {
+        1.0 - playout(&mut next_state)
+    }
+}
+
+/// Flat Monte Carlo over every legal action with Sequential Halving: each round spreads an equal
+/// share of the playouts over the remaining actions and keeps the better half, until one is left.
+/// Every action gets at least one playout, even when there are more actions than playouts. Like
+/// the tree searches, it always calls the lines it completes. Finished games have no statistics.
+pub fn primitive_monte_carlo_search(state: &State, playout_number: usize) -> Vec<ActionStatistics> {
+    let mut statistics: Vec<ActionStatistics> = state
+        .legal_actions()
+        .into_iter()
+        .filter(|&(place, piece)| {
+            piece.is_none() || place.is_none_or(|(h, w)| !state.can_put_then_win(h, w))
+        })
+        .map(|action| ActionStatistics {
+            action,
+            trials: 0,
+            value: 0.0,
+        })
+        .collect();
+    if statistics.is_empty() {
+        return statistics;
+    }
+    let mut remaining: Vec<usize> = (0..statistics.len()).collect();
+    let rounds = max(
+        usize::BITS - statistics.len().saturating_sub(1).leading_zeros(),
+        1,
+    ) as usize;
+    for _ in 0..rounds {
+        let trials = max(playout_number / (remaining.len() * rounds), 1);
+        for &i in &remaining {
+            for _ in 0..trials {
+                statistics[i].value += action_value(state, statistics[i].action);
             }
+            statistics[i].trials += trials as i32;
         }
-        _ => panic!("unreachable error"),
+        let mean = |i: usize| statistics[i].value / statistics[i].trials as f64;
+        remaining.sort_by(|&a, &b| mean(b).total_cmp(&mean(a)));
+        remaining.truncate(remaining.len().div_ceil(2));
+    }
+    for s in &mut statistics {
+        s.value /= max(s.trials, 1) as f64;
     }
+    statistics
 }
 
+/// The action left by [`primitive_monte_carlo_search`]. Panics when the game is over.
 pub fn primitive_monte_carlo_action(
     state: &State,
     playout_number: usize,
 ) -> (Option<(usize, usize)>, Option<Piece>) {
-    let mut legal_places = Vec::new();
-    if !state.is_first_turn() {
-        legal_places = state.legal_placements();
-    }
-    let mut legal_pieces = Vec::new();
-    if !state.is_last_turn() {
-        legal_pieces = state.legal_pieces();
-    }
-
-    let mut values = vec![vec![0.0; max(legal_pieces.len(), 1)]; max(legal_places.len(), 1)];
-    let mut cnts = vec![vec![0usize; max(legal_pieces.len(), 1)]; max(legal_places.len(), 1)];
-    for cnt in 0..playout_number {
-        let mut next_state = *state;
-        let next_state = &mut next_state;
-
-        if !legal_places.is_empty() {
-            let place = legal_places[cnt % legal_places.len()];
-            next_state.put_piece(place.0, place.1);
-        };
-        if !legal_pieces.is_empty() {
-            let piece = legal_pieces[cnt % legal_pieces.len()];
-            next_state.select_piece(piece);
-        };
-
-        values[cnt % max(legal_places.len(), 1)][cnt % max(legal_pieces.len(), 1)] +=
-            1.0 - playout(next_state);
-        cnts[cnt % max(legal_places.len(), 1)][cnt % max(legal_pieces.len(), 1)] += 1;
-    }
-
-    let mut best_action_idx = (usize::MAX, usize::MAX);
-    let mut best_score = f64::NEG_INFINITY;
-    for place_idx in 0..max(legal_places.len(), 1) {
-        for piece_idx in 0..max(legal_pieces.len(), 1) {
-            let value_mean = values[place_idx][piece_idx] / cnts[place_idx][piece_idx] as f64;
-            if value_mean > best_score {
-                best_score = value_mean;
-                best_action_idx = (place_idx, piece_idx);
-            }
-        }
+    assert!(
+        !state.is_done(),
+        "no action is left in a finished game: {}",
+        state.notation()
+    );
+    if state.can_claim() && completes_lines(state) {
+        return (None, None);
     }
-    let place = if legal_places.is_empty() {
-        None
-    } else {
-        Some(legal_places[best_action_idx.0])
-    };
-    let piece = if legal_pieces.is_empty() {
-        None
-    } else {
-        Some(legal_pieces[best_action_idx.1])
-    };
-    (place, piece)
+    primitive_monte_carlo_search(state, playout_number)
+        .into_iter()
+        .max_by(|a, b| a.trials.cmp(&b.trials).then(a.value.total_cmp(&b.value)))
+        .unwrap()
+        .action
 }
 
 const C: f64 = 1.0;
 
 const EXPAND_THRESHOLD: i32 = 10;
 
+/// The default number of trials at which RAVE and UCT values weigh the same.
+pub const RAVE_EQUIVALENCE: f64 = 10.0;
+
+// The weight of the network's priors against the values in PUCT selection.
+const PUCT_C: f64 = 1.5;
+
 struct Node {
     state: State,
     child_nodes: Vec<Node>,
     trials: i32,
     cumulative_value: f64,
+    rave_trials: i32,
+    rave_cumulative_value: f64,
     put_place: Option<(usize, usize)>,
     selected_piece: Option<Piece>,
+    // The network's probability of this node's action, for PUCT searches.
+    prior: f64,
 }
 
 impl Node {
@@ -120,16 +170,36 @@ impl Node {
             child_nodes: Vec::new(),
             trials: 0,
             cumulative_value: 0.0,
+            rave_trials: 0,
+            rave_cumulative_value: 0.0,
             put_place: None,
             selected_piece: None,
+            prior: 0.0,
         }
     }
 
     pub fn expand(&mut self) {
         self.child_nodes.clear();
+        if self.state.can_claim() {
+            self.child_nodes.push(Node::new(self.state));
+            self.child_nodes
+                .last_mut()
+                .unwrap()
+                .state
+                .apply_action((None, None));
+        }
+        if self.state.is_first_turn() {
+            for &s in &self.state.legal_pieces() {
+                self.child_nodes.push(Node::new(self.state));
+                self.child_nodes.last_mut().unwrap().state.select_piece(s);
+                self.child_nodes.last_mut().unwrap().selected_piece = Some(s);
+            }
+            return;
+        }
         for p in self.state.legal_placements() {
-            if self.state.legal_placements().is_empty() {
-                self.child_nodes.push(Node::new(self.state.clone()));
+            // Winning placements end the game, so they are never followed by a piece.
+            if self.state.is_last_turn() || self.state.can_put_then_win(p.0, p.1) {
+                self.child_nodes.push(Node::new(self.state));
                 self.child_nodes
                     .last_mut()
                     .unwrap()
@@ -138,7 +208,7 @@ impl Node {
                 self.child_nodes.last_mut().unwrap().put_place = Some(p);
             } else {
                 for &s in &self.state.legal_pieces() {
-                    self.child_nodes.push(Node::new(self.state.clone()));
+                    self.child_nodes.push(Node::new(self.state));
                     self.child_nodes
                         .last_mut()
                         .unwrap()
@@ -177,19 +247,79 @@ impl Node {
         best_action_idx
     }
 
+    // Children are ranked by their value plus an exploration bonus that follows their prior.
+    // Children without trials take the value of this node.
+    fn next_puct_child_node_idx(&self) -> usize {
+        let parent_value = self.cumulative_value / self.trials as f64;
+        let exploration = PUCT_C * (self.trials as f64).sqrt();
+        let mut best_value = f64::NEG_INFINITY;
+        let mut best_action_idx = usize::MAX;
+        for (i, child_node) in self.child_nodes.iter().enumerate() {
+            let value = if child_node.trials > 0 {
+                1.0 - child_node.cumulative_value / child_node.trials as f64
+            } else {
+                parent_value
+            };
+            let puct_value =
+                value + exploration * child_node.prior / (1 + child_node.trials) as f64;
+            if puct_value > best_value {
+                best_action_idx = i;
+                best_value = puct_value;
+            }
+        }
+        best_action_idx
+    }
+
+    // Like `evaluate`, but a new leaf is expanded at once and valued by the network instead of a
+    // playout.
+    pub fn evaluate_puct(&mut self, network: &Network) -> f64 {
+        if self.state.is_done() {
+            let value = 1.0 - self.state.reward();
+            self.trials += 1;
+            self.cumulative_value += value;
+            return value;
+        }
+
+        if self.child_nodes.is_empty() {
+            let (priors, value) = network.evaluate(&self.state);
+            let mut policy = vec![0.0; POLICY_SIZE];
+            for (action, prior) in priors {
+                policy[action_index(action)] = prior as f64;
+            }
+            self.expand();
+            for child_node in &mut self.child_nodes {
+                child_node.prior =
+                    policy[action_index((child_node.put_place, child_node.selected_piece))];
+            }
+            let value = value as f64;
+            self.trials += 1;
+            self.cumulative_value += value;
+            return value;
+        }
+
+        let next_child_idx = self.next_puct_child_node_idx();
+        let value = 1.0 - self.child_nodes[next_child_idx].evaluate_puct(network);
+        self.trials += 1;
+        self.cumulative_value += value;
+        value
+    }
+
     pub fn evaluate(&mut self) -> f64 {
         if self.state.is_done() {
-            let value = match self.state.get_winning_status() {
-                WinningStatus::WIN => 0.0,
-                _ => 0.5,
-            };
+            let value = 1.0 - self.state.reward();
+            self.trials += 1;
+            self.cumulative_value += value;
+            return value;
+        }
+
+        if let Some(value) = probe(&self.state) {
             self.trials += 1;
             self.cumulative_value += value;
             return value;
         }
 
         if self.child_nodes.is_empty() {
-            let mut state_copy = self.state.clone();
+            let mut state_copy = self.state;
             let value = playout(&mut state_copy);
             self.trials += 1;
             self.cumulative_value += value;
@@ -206,42 +336,131 @@ impl Node {
         self.cumulative_value += value;
         value
     }
+
+    // Children without trials are ranked by their RAVE value alone, so placements and pieces
+    // that did well elsewhere in the tree are tried first.
+    fn next_rave_child_node_idx(&self, rave_equivalence: f64) -> usize {
+        for (i, child_node) in self.child_nodes.iter().enumerate() {
+            if child_node.trials == 0 && child_node.rave_trials == 0 {
+                return i;
+            }
+        }
+        let mut trials = 0;
+        for child_node in &self.child_nodes {
+            trials += child_node.trials;
+        }
+        let exploration = 2.0 * (max(trials, 1) as f64).ln();
+        let mut best_value = f64::NEG_INFINITY;
+        let mut best_action_idx = usize::MAX;
+        for (i, child_node) in self.child_nodes.iter().enumerate() {
+            let rave_value = if child_node.rave_trials > 0 {
+                1.0 - child_node.rave_cumulative_value / child_node.rave_trials as f64
+            } else {
+                0.5
+            };
+            let value = if child_node.trials == 0 {
+                rave_value + C * exploration.sqrt()
+            } else {
+                let n = child_node.trials as f64;
+                let beta = (rave_equivalence / (3.0 * n + rave_equivalence)).sqrt();
+                let uct_value = 1.0 - child_node.cumulative_value / n;
+                (1.0 - beta) * uct_value + beta * rave_value + C * (exploration / n).sqrt()
+            };
+
+            if value > best_value {
+                best_action_idx = i;
+                best_value = value;
+            }
+        }
+        best_action_idx
+    }
+
+    // `moves` starts with the action chosen at this node, so every second entry was played by the
+    // player to move here. A child counts once per playout, even when its placement and its piece
+    // were both played.
+    fn update_rave(&mut self, moves: &[Action], value: f64) {
+        for child_node in &mut self.child_nodes {
+            let played = moves.iter().step_by(2).any(|&(place, piece)| {
+                (place.is_some() && place == child_node.put_place)
+                    || (piece.is_some() && piece == child_node.selected_piece)
+            });
+            if played {
+                child_node.rave_trials += 1;
+                child_node.rave_cumulative_value += 1.0 - value;
+            }
+        }
+    }
+
+    pub fn evaluate_rave(&mut self, rave_equivalence: f64, trace: &mut Vec<Action>) -> f64 {
+        if self.state.is_done() {
+            let value = 1.0 - self.state.reward();
+            self.trials += 1;
+            self.cumulative_value += value;
+            return value;
+        }
+
+        if let Some(value) = probe(&self.state) {
+            self.trials += 1;
+            self.cumulative_value += value;
+            return value;
+        }
+
+        if self.child_nodes.is_empty() {
+            let mut state_copy = self.state;
+            let value = playout_with_trace(&mut state_copy, Some(trace));
+            self.trials += 1;
+            self.cumulative_value += value;
+
+            if self.trials == EXPAND_THRESHOLD {
+                self.expand();
+            }
+            return value;
+        }
+
+        let start = trace.len();
+        let next_child_idx = self.next_rave_child_node_idx(rave_equivalence);
+        let child_node = &mut self.child_nodes[next_child_idx];
+        trace.push((child_node.put_place, child_node.selected_piece));
+        let value = 1.0 - child_node.evaluate_rave(rave_equivalence, trace);
+        self.update_rave(&trace[start..], value);
+        self.trials += 1;
+        self.cumulative_value += value;
+        value
+    }
 }
 
-pub fn mcts_action(
-    state: &State,
-    playout_number: usize,
-) -> (Option<(usize, usize)>, Option<Piece>) {
+/// A claim of an open line, the opening book move, a random first piece or the only placement on
+/// the last turn, which need no search.
+pub fn forced_action(state: &State) -> Option<Action> {
+    if state.can_claim() && completes_lines(state) {
+        return Some((None, None));
+    }
+
+    if let Some(action) = book_move(state) {
+        return Some(action);
+    }
+
     if state.is_first_turn() {
         let mut rng = thread_rng();
         let legal_select = state.legal_pieces();
-        return (
+        return Some((
             None,
             Some(legal_select[rng.gen::<usize>() % legal_select.len()]),
-        );
+        ));
     }
 
     if state.is_last_turn() {
         let legal_put = state.legal_placements()[0];
-        return (Some(legal_put), None);
+        return Some((Some(legal_put), None));
     }
+    None
+}
 
-    let mut root_node = Node::new(*state);
-    root_node.expand();
-
-    for _ in 0..playout_number {
-        root_node.evaluate();
-    }
-
-    let put = state.legal_placements();
-    let select = state.legal_pieces();
-
+fn most_visited_action(root_node: &Node) -> (Option<(usize, usize)>, Option<Piece>) {
     let mut best_action_search_number = i32::MIN;
     let mut best_action_put = None;
     let mut best_action_select = None;
 
-    assert_eq!(put.len() * select.len(), root_node.child_nodes.len());
-
     for i in 0..root_node.child_nodes.len() {
         let trials = root_node.child_nodes[i].trials;
         if trials > best_action_search_number {
@@ -253,3 +472,175 @@ pub fn mcts_action(
 
     (best_action_put, best_action_select)
 }
+
+/// The visits of one root action and its value for the player to move.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ActionStatistics {
+    pub action: Action,
+    pub trials: i32,
+    pub value: f64,
+}
+
+const REPORT_INTERVAL: usize = 10000;
+
+/// When a search stops.
+pub enum SearchLimit {
+    Playouts(usize),
+    Time(Duration),
+}
+
+fn root_statistics(root_node: &Node) -> Vec<ActionStatistics> {
+    root_node
+        .child_nodes
+        .iter()
+        .map(|child_node| ActionStatistics {
+            action: (child_node.put_place, child_node.selected_piece),
+            trials: child_node.trials,
+            value: if child_node.trials > 0 {
+                1.0 - child_node.cumulative_value / child_node.trials as f64
+            } else {
+                0.0
+            },
+        })
+        .collect()
+}
+
+/// Searches until the limit is reached or `stop` is set, and calls `report` with the number of
+/// playouts so far and the root statistics every `REPORT_INTERVAL` playouts.
+pub fn mcts_search_until(
+    state: &State,
+    limit: &SearchLimit,
+    stop: &AtomicBool,
+    report: &mut dyn FnMut(usize, &[ActionStatistics]),
+) -> Vec<ActionStatistics> {
+    let start = Instant::now();
+    let mut root_node = Node::new(*state);
+    root_node.expand();
+
+    let mut playouts = 0;
+    while !stop.load(Ordering::Relaxed) {
+        match *limit {
+            SearchLimit::Playouts(playout_number) if playouts >= playout_number => break,
+            SearchLimit::Time(duration) if start.elapsed() >= duration => break,
+            _ => {}
+        }
+        root_node.evaluate();
+        playouts += 1;
+        if playouts % REPORT_INTERVAL == 0 {
+            report(playouts, &root_statistics(&root_node));
+        }
+    }
+    root_statistics(&root_node)
+}
+
+/// The root statistics after a fixed number of playouts.
+pub fn mcts_search(state: &State, playout_number: usize) -> Vec<ActionStatistics> {
+    mcts_search_until(
+        state,
+        &SearchLimit::Playouts(playout_number),
+        &AtomicBool::new(false),
+        &mut |_, _| {},
+    )
+}
+
+/// The root statistics of a PUCT search guided by `network` after a fixed number of playouts,
+/// each of which ends at a network evaluation or the end of the game.
+pub fn puct_search(
+    state: &State,
+    network: &Network,
+    playout_number: usize,
+) -> Vec<ActionStatistics> {
+    let mut root_node = Node::new(*state);
+    // The first evaluation expands the root.
+    root_node.evaluate_puct(network);
+    for _ in 0..playout_number {
+        root_node.evaluate_puct(network);
+    }
+    root_statistics(&root_node)
+}
+
+/// The most visited action of a UCT search. Panics when the game is over.
+pub fn mcts_action(
+    state: &State,
+    playout_number: usize,
+) -> (Option<(usize, usize)>, Option<Piece>) {
+    assert!(
+        !state.is_done(),
+        "no action is left in a finished game: {}",
+        state.notation()
+    );
+    if let Some(action) = forced_action(state) {
+        return action;
+    }
+
+    let mut root_node = Node::new(*state);
+    root_node.expand();
+
+    for _ in 0..playout_number {
+        root_node.evaluate();
+    }
+
+    most_visited_action(&root_node)
+}
+
+/// The most visited action of a search that shares playout results between equal placements and
+/// pieces (RAVE). Panics when the game is over.
+pub fn mcts_rave_action(
+    state: &State,
+    playout_number: usize,
+    rave_equivalence: f64,
+) -> (Option<(usize, usize)>, Option<Piece>) {
+    assert!(
+        !state.is_done(),
+        "no action is left in a finished game: {}",
+        state.notation()
+    );
+    if let Some(action) = forced_action(state) {
+        return action;
+    }
+
+    let mut root_node = Node::new(*state);
+    root_node.expand();
+
+    let mut trace = Vec::new();
+    for _ in 0..playout_number {
+        trace.clear();
+        root_node.evaluate_rave(rave_equivalence, &mut trace);
+    }
+
+    most_visited_action(&root_node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rave_counts_each_child_once_per_playout() {
+        let state =
+            State::from_notation("BSSFWCTHBCSH./..BSTHWCSF/.BCTFWSTF./WSSH... BCTH").unwrap();
+        let mut node = Node::new(state);
+        node.expand();
+        let (place, piece) = (Some((0, 3)), Some(node.state.legal_pieces()[0]));
+        // The placement and the piece of one child, played at different turns of the same player.
+        let moves = [
+            (place, state.legal_pieces().get(1).copied()),
+            (Some((1, 0)), state.legal_pieces().get(2).copied()),
+            (Some((2, 0)), piece),
+        ];
+        node.update_rave(&moves, 0.0);
+        for child_node in &node.child_nodes {
+            let expected = [place, moves[2].0].contains(&child_node.put_place)
+                || child_node.selected_piece == moves[0].1
+                || child_node.selected_piece == piece;
+            assert_eq!(child_node.rave_trials, i32::from(expected));
+        }
+        let child_node = node
+            .child_nodes
+            .iter()
+            .find(|child_node| child_node.put_place == place && child_node.selected_piece == piece)
+            .unwrap();
+        assert_eq!(child_node.rave_cumulative_value, 1.0);
+    }
+}
@@ -0,0 +1,291 @@
+//! Network play over TCP. Every message is one line of space separated words.
+//!
+//! ```text
+//! client -> server  hello quarto/1 <name>             handshake, the first client plays 1p
+//! server -> client  welcome <1p|2p>
+//! server -> client  error <reason>                    before closing a refused connection
+//! server -> mover   position <notation>               see `State::notation`
+//! server -> mover   go
+//! mover -> server   action <cell|-> <piece|->         see `format_action`
+//! mover -> server   resign
+//! server -> both    action <1p|2p> <cell|-> <piece|->  every accepted action
+//! server -> both    result <1p|2p|draw> <reason>      reason: quarto, full, resign, illegal or
+//!                                                     time; then the server closes
+//! ```
+//!
+//! The server holds the authoritative `State`. An illegal action forfeits the game, and a malformed
+//! line or a closed connection counts as resigning. A mover that does not answer within the timeout
+//! forfeits on time, and a connection that sends no hello within it is refused.
+
+use crate::play::{Decision, GameRecord, Player, PLAYER_NAMES};
+use crate::quarto::{format_action, parse_action, State};
+use crate::render::{render, RenderOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const PROTOCOL: &str = "quarto/1";
+
+/// How long `serve` waits for a hello or an action.
+pub const TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        Ok(Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    pub fn send(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()
+    }
+
+    // `None` when the peer closed the connection.
+    pub fn receive(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim().to_string()))
+    }
+}
+
+pub struct Outcome {
+    pub winner: Option<usize>,
+    pub reason: String,
+}
+
+impl Outcome {
+    fn message(&self) -> String {
+        format!(
+            "result {} {}",
+            self.winner.map_or("draw", |player| PLAYER_NAMES[player]),
+            self.reason
+        )
+    }
+}
+
+// The server side of a connected client.
+pub struct RemotePlayer {
+    pub name: String,
+    connection: Connection,
+}
+
+impl RemotePlayer {
+    pub fn accept(listener: &TcpListener, player: usize, timeout: Duration) -> io::Result<Self> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+            let mut connection = Connection::new(stream)?;
+            let hello = match connection.receive() {
+                Ok(hello) => hello.unwrap_or_default(),
+                Err(e) if is_timeout(&e) => {
+                    let _ = connection.send("error timed out waiting for hello");
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            let words: Vec<&str> = hello.split_whitespace().collect();
+            match words.as_slice() {
+                ["hello", PROTOCOL, name] => {
+                    connection.send(&format!("welcome {}", PLAYER_NAMES[player]))?;
+                    return Ok(RemotePlayer {
+                        name: name.to_string(),
+                        connection,
+                    });
+                }
+                _ => {
+                    let _ = connection.send("error expected `hello quarto/1 <name>`");
+                }
+            }
+        }
+    }
+}
+
+impl Player for RemotePlayer {
+    fn decide(&mut self, state: &State) -> Decision {
+        let reply = self
+            .connection
+            .send(&format!("position {}", state.notation()))
+            .and_then(|_| self.connection.send("go"))
+            .and_then(|_| self.connection.receive());
+        let line = match reply {
+            Ok(Some(line)) => line,
+            Err(e) if is_timeout(&e) => return Decision::Forfeit(String::from("time")),
+            _ => return Decision::Resign,
+        };
+        if line == "resign" {
+            return Decision::Resign;
+        }
+        match line.strip_prefix("action ").and_then(parse_action) {
+            Some(action) => Decision::Act(action),
+            None => Decision::Resign,
+        }
+    }
+}
+
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+// Plays one game between the first two clients, writing who joined and the result to `log`.
+pub fn serve(listener: &TcpListener, log: &mut dyn Write) -> io::Result<(GameRecord, Outcome)> {
+    serve_with_timeout(listener, TIMEOUT, log)
+}
+
+pub fn serve_with_timeout(
+    listener: &TcpListener,
+    timeout: Duration,
+    log: &mut dyn Write,
+) -> io::Result<(GameRecord, Outcome)> {
+    let mut players = Vec::new();
+    for (player, player_name) in PLAYER_NAMES.iter().enumerate() {
+        let remote = RemotePlayer::accept(listener, player, timeout)?;
+        writeln!(log, "{} joined as {}", remote.name, player_name)?;
+        players.push(remote);
+    }
+
+    let mut record = GameRecord::new();
+    let mut state = State::new();
+    let outcome = loop {
+        let mover = record.actions.len() % 2;
+        let action = match players[mover].decide(&state) {
+            Decision::Act(action) if state.is_legal_action(action) => action,
+            Decision::Act(_) => {
+                record.resigned = Some(mover);
+                break Outcome {
+                    winner: Some(mover ^ 1),
+                    reason: String::from("illegal"),
+                };
+            }
+            Decision::Forfeit(reason) => {
+                record.resigned = Some(mover);
+                break Outcome {
+                    winner: Some(mover ^ 1),
+                    reason,
+                };
+            }
+            _ => {
+                record.resigned = Some(mover);
+                break Outcome {
+                    winner: Some(mover ^ 1),
+                    reason: String::from("resign"),
+                };
+            }
+        };
+        record.actions.push(action);
+        state.apply_action(action);
+        let message = format!("action {} {}", PLAYER_NAMES[mover], format_action(action));
+        for player in players.iter_mut() {
+            // A dropped spectator notices at its next `go` and forfeits there.
+            let _ = player.connection.send(&message);
+        }
+        if state.is_done() {
+            break if state.can_win() {
+                Outcome {
+                    winner: Some(mover),
+                    reason: String::from("quarto"),
+                }
+            } else {
+                Outcome {
+                    winner: None,
+                    reason: String::from("full"),
+                }
+            };
+        }
+    };
+
+    let message = outcome.message();
+    writeln!(log, "{}", message)?;
+    for player in players.iter_mut() {
+        let _ = player.connection.send(&message);
+    }
+    Ok((record, outcome))
+}
+
+fn protocol_error(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unexpected message: {}", line),
+    )
+}
+
+// Plays one game on a server with a local player, writing the boards and the messages of the
+// server to `log`. Undo and save are not available over the network, so the player is asked again.
+pub fn connect<A: ToSocketAddrs>(
+    address: A,
+    name: &str,
+    player: &mut dyn Player,
+    log: &mut dyn Write,
+) -> io::Result<Outcome> {
+    let mut connection = Connection::new(TcpStream::connect(address)?)?;
+    connection.send(&format!("hello {} {}", PROTOCOL, name))?;
+    let mut state = State::new();
+    loop {
+        let line = match connection.receive()? {
+            Some(line) => line,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the server closed the connection",
+                ))
+            }
+        };
+        let (command, rest) = line.split_once(' ').unwrap_or((&line, ""));
+        match command {
+            "welcome" => writeln!(log, "playing as {}", rest)?,
+            "position" => {
+                state = State::from_notation(rest).ok_or_else(|| protocol_error(&line))?
+            }
+            "go" => {
+                writeln!(log, "{}", render(&state, &RenderOptions::detect()))?;
+                if let Some(piece) = state.selected_piece() {
+                    writeln!(log, "in hand: {}", piece)?;
+                }
+                let reply = loop {
+                    match player.decide(&state) {
+                        Decision::Act(action) => {
+                            let action = state.normalize_action(action);
+                            break format!("action {}", format_action(action));
+                        }
+                        Decision::Resign | Decision::Forfeit(_) => break String::from("resign"),
+                        _ => writeln!(log, "undo and save are not available in network games")?,
+                    }
+                };
+                connection.send(&reply)?;
+            }
+            "action" => writeln!(log, "{}", rest)?,
+            "result" => {
+                writeln!(log, "{}", line)?;
+                let mut words = rest.split_whitespace();
+                let winner = match words.next() {
+                    Some("draw") => None,
+                    Some(name) => Some(
+                        PLAYER_NAMES
+                            .iter()
+                            .position(|&n| n == name)
+                            .ok_or_else(|| protocol_error(&line))?,
+                    ),
+                    None => return Err(protocol_error(&line)),
+                };
+                return Ok(Outcome {
+                    winner,
+                    reason: words.next().unwrap_or_default().to_string(),
+                });
+            }
+            "error" => return Err(io::Error::other(rest.to_string())),
+            _ => return Err(protocol_error(&line)),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! A small neural network evaluated on the CPU, which gives the PUCT search in
+//! [`crate::montecarlo`] action priors and position values in place of random playouts.
+
+use crate::montecarlo::{forced_action, puct_search};
+use crate::play::{Decision, Player};
+use crate::quarto::{Action, Piece, State};
+use crate::rng::thread_rng;
+use rand::Rng;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+const SIZE: usize = State::SIZE;
+const PIECES: usize = State::PIECE_NUMBER;
+const ATTRIBUTES: usize = 4;
+
+/// The length of [`encode`]'s output: two planes per attribute, the piece in hand and the unused
+/// pieces.
+pub const INPUT_SIZE: usize = 2 * ATTRIBUTES * SIZE * SIZE + 2 * PIECES;
+
+/// The length of the policy, one entry per (placement or none, piece or none) pair.
+pub const POLICY_SIZE: usize = (SIZE * SIZE + 1) * (PIECES + 1);
+
+/// Encodes a position for the player to move. For each attribute, one plane marks the cells whose
+/// piece has it and one the cells whose piece does not, each plane row by row. A one-hot vector of
+/// the piece in hand and a mask of the unused pieces follow, both by `Piece::to_index`.
+pub fn encode(state: &State) -> Vec<f32> {
+    let mut input = vec![0.0; INPUT_SIZE];
+    for h in 0..SIZE {
+        for w in 0..SIZE {
+            if let Some(piece) = state.get_piece(h, w) {
+                for i in 0..ATTRIBUTES {
+                    let plane = 2 * i + usize::from(!piece.attribute(i));
+                    input[plane * SIZE * SIZE + h * SIZE + w] = 1.0;
+                }
+            }
+        }
+    }
+    let hand = 2 * ATTRIBUTES * SIZE * SIZE;
+    if let Some(piece) = state.selected_piece() {
+        input[hand + piece.to_index()] = 1.0;
+    }
+    for piece in state.legal_pieces() {
+        input[hand + PIECES + piece.to_index()] = 1.0;
+    }
+    input
+}
+
+/// The policy entry of an action. Placements count row by row and pieces by `Piece::to_index`,
+/// with the last of each for none.
+pub fn action_index((place, piece): Action) -> usize {
+    let place = place.map_or(SIZE * SIZE, |(h, w)| h * SIZE + w);
+    let piece = piece.map_or(PIECES, |piece| piece.to_index());
+    place * (PIECES + 1) + piece
+}
+
+/// The action of a policy entry, the inverse of [`action_index`].
+pub fn index_action(index: usize) -> Action {
+    let (place, piece) = (index / (PIECES + 1), index % (PIECES + 1));
+    (
+        (place < SIZE * SIZE).then_some((place / SIZE, place % SIZE)),
+        (piece < PIECES).then(|| Piece::from_index(piece)),
+    )
+}
+
+/// A fully connected layer with `outputs` rows of `inputs` weights.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dense {
+    pub inputs: usize,
+    pub outputs: usize,
+    pub weights: Vec<f32>,
+    pub biases: Vec<f32>,
+}
+
+impl Dense {
+    /// Uniform He initialization, for layers followed by a ReLU.
+    pub fn random(inputs: usize, outputs: usize) -> Self {
+        let mut rng = thread_rng();
+        let bound = (6.0 / inputs as f32).sqrt();
+        Dense {
+            inputs,
+            outputs,
+            weights: (0..inputs * outputs)
+                .map(|_| rng.gen_range(-bound..bound))
+                .collect(),
+            biases: vec![0.0; outputs],
+        }
+    }
+
+    pub fn forward(&self, input: &[f32]) -> Vec<f32> {
+        self.weights
+            .chunks_exact(self.inputs)
+            .zip(&self.biases)
+            .map(|(row, bias)| bias + row.iter().zip(input).map(|(w, x)| w * x).sum::<f32>())
+            .collect()
+    }
+}
+
+/// A trunk of ReLU layers with a policy head of logits and a value head.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    pub trunk: Vec<Dense>,
+    pub policy: Dense,
+    pub value: Dense,
+}
+
+// Weights files are little endian: the magic `QNET`, the format version and the number of trunk
+// layers as u32, then every trunk layer, the policy head and the value head, each as its inputs
+// and outputs as u32 followed by its weights row by row and its biases as f32.
+const MAGIC: &[u8; 4] = b"QNET";
+const VERSION: u32 = 1;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32s<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<f32>> {
+    let mut bytes = vec![0; len * 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect())
+}
+
+fn read_layer<R: Read>(reader: &mut R) -> io::Result<Dense> {
+    let inputs = read_u32(reader)? as usize;
+    let outputs = read_u32(reader)? as usize;
+    if inputs == 0 || outputs == 0 || inputs * outputs > 1 << 26 {
+        return Err(invalid("invalid layer size"));
+    }
+    Ok(Dense {
+        inputs,
+        outputs,
+        weights: read_f32s(reader, inputs * outputs)?,
+        biases: read_f32s(reader, outputs)?,
+    })
+}
+
+fn write_layer<W: Write>(writer: &mut W, layer: &Dense) -> io::Result<()> {
+    writer.write_all(&(layer.inputs as u32).to_le_bytes())?;
+    writer.write_all(&(layer.outputs as u32).to_le_bytes())?;
+    for value in layer.weights.iter().chain(&layer.biases) {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+impl Network {
+    /// A randomly initialized network with trunk layers of the given widths.
+    pub fn random(hidden: &[usize]) -> Self {
+        let mut trunk = Vec::new();
+        let mut inputs = INPUT_SIZE;
+        for &outputs in hidden {
+            trunk.push(Dense::random(inputs, outputs));
+            inputs = outputs;
+        }
+        Network {
+            trunk,
+            policy: Dense::random(inputs, POLICY_SIZE),
+            value: Dense::random(inputs, 1),
+        }
+    }
+
+    /// The policy logits and the value for the player to move, from 0 for a loss to 1 for a win.
+    pub fn forward(&self, input: &[f32]) -> (Vec<f32>, f32) {
+        let mut hidden = input.to_vec();
+        for layer in &self.trunk {
+            hidden = layer.forward(&hidden);
+            for x in &mut hidden {
+                *x = x.max(0.0);
+            }
+        }
+        let value = self.value.forward(&hidden)[0];
+        (self.policy.forward(&hidden), 1.0 / (1.0 + (-value).exp()))
+    }
+
+    /// The priors of the legal actions, a softmax of their logits, and the value of the position.
+    pub fn evaluate(&self, state: &State) -> (Vec<(Action, f32)>, f32) {
+        let (logits, value) = self.forward(&encode(state));
+        let actions = state.legal_actions();
+        let max = actions
+            .iter()
+            .map(|&action| logits[action_index(action)])
+            .fold(f32::NEG_INFINITY, f32::max);
+        let exps: Vec<f32> = actions
+            .iter()
+            .map(|&action| (logits[action_index(action)] - max).exp())
+            .collect();
+        let total: f32 = exps.iter().sum();
+        let priors = actions
+            .into_iter()
+            .zip(exps)
+            .map(|(action, exp)| (action, exp / total))
+            .collect();
+        (priors, value)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(self.trunk.len() as u32).to_le_bytes())?;
+        for layer in self.trunk.iter().chain([&self.policy, &self.value]) {
+            write_layer(&mut writer, layer)?;
+        }
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a network weights file"));
+        }
+        if read_u32(&mut reader)? != VERSION {
+            return Err(invalid("unsupported weights file version"));
+        }
+        let depth = read_u32(&mut reader)? as usize;
+        let mut trunk = Vec::new();
+        for _ in 0..depth {
+            trunk.push(read_layer(&mut reader)?);
+        }
+        let network = Network {
+            trunk,
+            policy: read_layer(&mut reader)?,
+            value: read_layer(&mut reader)?,
+        };
+        let mut inputs = INPUT_SIZE;
+        for layer in &network.trunk {
+            if layer.inputs != inputs {
+                return Err(invalid("layer sizes do not match"));
+            }
+            inputs = layer.outputs;
+        }
+        if network.policy.inputs != inputs
+            || network.policy.outputs != POLICY_SIZE
+            || network.value.inputs != inputs
+            || network.value.outputs != 1
+        {
+            return Err(invalid("head sizes do not match"));
+        }
+        Ok(network)
+    }
+}
+
+/// The most visited action of a PUCT search guided by `network`.
+pub fn puct_action(state: &State, network: &Network, playout_number: usize) -> Action {
+    if let Some(action) = forced_action(state) {
+        return action;
+    }
+    puct_search(state, network, playout_number)
+        .into_iter()
+        .max_by_key(|statistics| statistics.trials)
+        .unwrap()
+        .action
+}
+
+/// Plays [`puct_action`] with a fixed number of playouts.
+pub struct NetworkPlayer {
+    pub network: Network,
+    pub playout_number: usize,
+}
+
+impl Player for NetworkPlayer {
+    fn decide(&mut self, state: &State) -> Decision {
+        Decision::Act(puct_action(state, &self.network, self.playout_number))
+    }
+}
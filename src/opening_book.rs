@@ -0,0 +1,225 @@
+//! An opening book for the classic rules, generated by `quarto book` and consulted by
+//! [`forced_action`](crate::montecarlo::forced_action) before any search.
+//!
+//! Positions are keyed by `State::canonical` over `Symmetry::all`, so one entry serves every
+//! symmetric position, and each holds a few weighted moves to pick from at random. Moves that are
+//! not legal in the position looked up, as in a stale or edited book, are never played.
+
+use crate::montecarlo::mcts_search;
+use crate::quarto::{Action, Piece, RuleSet, State, Symmetry};
+use crate::rng::thread_rng;
+use rand::Rng;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::sync::{Mutex, OnceLock};
+
+const BOOK_WIDTH: usize = 4;
+
+const HEADER: &str = "# quarto opening book v1";
+
+// The most canonical keys remembered between lookups before they are forgotten.
+const CACHE_LIMIT: usize = 1 << 16;
+
+static BOOK: OnceLock<OpeningBook> = OnceLock::new();
+
+// Positions are keyed by `State::canonical` over `Symmetry::all`, and the stored actions are
+// expressed in the coordinates of that canonical position.
+pub struct OpeningBook {
+    entries: HashMap<u128, Vec<(Action, u32)>>,
+    // Canonical keys and symmetries by `State::key`, as canonicalizing tries every symmetry.
+    canonical_keys: Mutex<HashMap<u128, (u128, Symmetry)>>,
+}
+
+impl OpeningBook {
+    /// Searches the positions reached by the best moves for `plies` plies. `progress` is called
+    /// after each ply with the ply and the number of positions so far.
+    pub fn generate(
+        plies: usize,
+        playout_number: usize,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Self {
+        let mut entries = HashMap::new();
+        let mut frontier = vec![State::new()];
+        for ply in 0..plies {
+            let mut next_frontier = Vec::new();
+            for state in frontier {
+                let (key, symmetry) = state.canonical(Symmetry::all());
+                if entries.contains_key(&key) {
+                    continue;
+                }
+                let canonical_state = state.transformed(&symmetry);
+                // Symmetric actions split the search effort between them, so their trials are
+                // merged under the first action that leads to each canonical position.
+                let mut merged: Vec<(u128, Action, u32)> = Vec::new();
+                for s in mcts_search(&canonical_state, playout_number) {
+                    let mut next_state = canonical_state;
+                    next_state.apply_action(s.action);
+                    let (next_key, _) = next_state.canonical(Symmetry::all());
+                    match merged.iter_mut().find(|(k, _, _)| *k == next_key) {
+                        Some((_, _, trials)) => *trials += s.trials as u32,
+                        None => merged.push((next_key, s.action, s.trials as u32)),
+                    }
+                }
+                merged.sort_by_key(|&(_, _, trials)| std::cmp::Reverse(trials));
+                let moves: Vec<(Action, u32)> = merged
+                    .iter()
+                    .take(BOOK_WIDTH)
+                    .filter(|&&(_, _, trials)| trials > 0)
+                    .map(|&(_, action, trials)| (action, trials))
+                    .collect();
+                for &(action, _) in &moves {
+                    let mut next_state = canonical_state;
+                    next_state.apply_action(action);
+                    if !next_state.is_done() {
+                        next_frontier.push(next_state);
+                    }
+                }
+                entries.insert(key, moves);
+            }
+            progress(ply, entries.len());
+            frontier = next_frontier;
+        }
+        OpeningBook::new(entries)
+    }
+
+    fn new(entries: HashMap<u128, Vec<(Action, u32)>>) -> Self {
+        OpeningBook {
+            entries,
+            canonical_keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn lookup(&self, state: &State) -> Option<Action> {
+        // The book was built for the classic rules.
+        if state.rules() != RuleSet::default() {
+            return None;
+        }
+        let (key, symmetry) = self.canonical(state);
+        let canonical_state = state.transformed(&symmetry);
+        let moves: Vec<(Action, u32)> = self
+            .entries
+            .get(&key)?
+            .iter()
+            .filter(|&&(action, _)| canonical_state.is_legal_action(action))
+            .map(|&(action, weight)| (symmetry.unmap_action(action), weight))
+            .collect();
+        let total: u32 = moves.iter().map(|&(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut choice = thread_rng().gen_range(0..total);
+        for (action, weight) in moves {
+            if choice < weight {
+                return Some(action);
+            }
+            choice -= weight;
+        }
+        None
+    }
+
+    fn canonical(&self, state: &State) -> (u128, Symmetry) {
+        let mut canonical_keys = self.canonical_keys.lock().unwrap();
+        if let Some(&canonical) = canonical_keys.get(&state.key()) {
+            return canonical;
+        }
+        if canonical_keys.len() >= CACHE_LIMIT {
+            canonical_keys.clear();
+        }
+        let canonical = state.canonical(Symmetry::all());
+        canonical_keys.insert(state.key(), canonical);
+        canonical
+    }
+
+    // One line per position: the canonical key in hex followed by `place piece weight` triples,
+    // where a place is written `h,w` and a missing place or piece is written `-`.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", HEADER)?;
+        let mut keys: Vec<&u128> = self.entries.keys().collect();
+        keys.sort();
+        for key in keys {
+            write!(writer, "{:x}", key)?;
+            for &((place, piece), weight) in &self.entries[key] {
+                match place {
+                    Some((h, w)) => write!(writer, " {},{}", h, w)?,
+                    None => write!(writer, " -")?,
+                }
+                match piece {
+                    Some(piece) => write!(writer, " {}", piece)?,
+                    None => write!(writer, " -")?,
+                }
+                write!(writer, " {}", weight)?;
+            }
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut entries = HashMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, moves) = parse_line(&line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid opening book line: {}", line),
+                )
+            })?;
+            entries.insert(key, moves);
+        }
+        Ok(OpeningBook::new(entries))
+    }
+}
+
+fn parse_line(line: &str) -> Option<(u128, Vec<(Action, u32)>)> {
+    let mut tokens = line.split_whitespace();
+    let key = u128::from_str_radix(tokens.next()?, 16).ok()?;
+    let tokens: Vec<&str> = tokens.collect();
+    if !tokens.len().is_multiple_of(3) {
+        return None;
+    }
+    let mut moves = Vec::new();
+    for chunk in tokens.chunks(3) {
+        let place = match chunk[0] {
+            "-" => None,
+            place => {
+                let (h, w) = place.split_once(',')?;
+                Some((h.parse().ok()?, w.parse().ok()?))
+            }
+        };
+        let piece = match chunk[1] {
+            "-" => None,
+            piece => Some(piece.parse::<Piece>().ok()?),
+        };
+        moves.push(((place, piece), chunk[2].parse().ok()?));
+    }
+    Some((key, moves))
+}
+
+pub fn load(path: &str) -> io::Result<()> {
+    let book = OpeningBook::load(path)?;
+    if BOOK.set(book).is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "opening book is already loaded",
+        ));
+    }
+    Ok(())
+}
+
+pub fn book_move(state: &State) -> Option<Action> {
+    BOOK.get()?.lookup(state)
+}
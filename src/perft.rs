@@ -0,0 +1,86 @@
+//! Move generation counts in the spirit of chess perft, for catching regressions in
+//! [`GameState`]'s move generation and win detection.
+
+use crate::quarto::{GameState, Symmetry};
+use std::collections::HashMap;
+
+/// The positions reached after a number of actions, and how many of them ended the game.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PerftCounts {
+    pub nodes: u64,
+    // Games ended by a completed line, whether it won or lost for its player.
+    pub wins: u64,
+    pub draws: u64,
+}
+
+impl PerftCounts {
+    fn add<const N: usize, const K: usize>(&mut self, state: &GameState<N, K>) {
+        self.nodes += 1;
+        if state.is_done() {
+            if state.can_win() {
+                self.wins += 1;
+            } else {
+                self.draws += 1;
+            }
+        }
+    }
+}
+
+/// Counts every sequence of legal actions from `state`, one entry per depth from 1 to `depth`.
+/// Finished games are counted at their depth and not expanded further.
+pub fn perft<const N: usize, const K: usize>(
+    state: &GameState<N, K>,
+    depth: usize,
+) -> Vec<PerftCounts> {
+    let mut counts = vec![PerftCounts::default(); depth];
+    count(state, &mut counts);
+    counts
+}
+
+fn count<const N: usize, const K: usize>(state: &GameState<N, K>, counts: &mut [PerftCounts]) {
+    let Some((first, rest)) = counts.split_first_mut() else {
+        return;
+    };
+    for action in state.legal_actions() {
+        let mut next_state = *state;
+        next_state.apply_action(action);
+        first.add(&next_state);
+        if !next_state.is_done() {
+            count(&next_state, rest);
+        }
+    }
+}
+
+/// Like [`perft`], but counts the distinct positions at each depth, with positions that map onto
+/// each other under `symmetries` counted once. The symmetries should keep the winning patterns of
+/// the rules, see [`Symmetry::keeps_patterns`]. Positions are told apart by their board and piece
+/// in hand only, so a line missed under the call rule is not.
+pub fn perft_unique<const N: usize, const K: usize>(
+    state: &GameState<N, K>,
+    depth: usize,
+    symmetries: &[Symmetry<N, K>],
+) -> Vec<PerftCounts> {
+    let mut counts = Vec::new();
+    let mut frontier = vec![*state];
+    for _ in 0..depth {
+        let mut positions = HashMap::new();
+        for state in &frontier {
+            for action in state.legal_actions() {
+                let mut next_state = *state;
+                next_state.apply_action(action);
+                let (key, _) = next_state.canonical(symmetries);
+                positions.entry(key).or_insert(next_state);
+            }
+        }
+        let mut depth_counts = PerftCounts::default();
+        for state in positions.values() {
+            depth_counts.add(state);
+        }
+        counts.push(depth_counts);
+        frontier = positions
+            .into_values()
+            .filter(|state| !state.is_done())
+            .collect();
+    }
+    counts
+}
@@ -1,184 +1,392 @@
-use crate::quarto::{Piece, State, WinningStatus};
+//! Local games between [`Player`]s, the human player of the text interface, and game records
+//! that are saved, loaded and replayed.
+
+use crate::coach::review;
+use crate::input::{parse_command, Command, HELP};
+use crate::montecarlo::mcts_action;
+use crate::quarto::{
+    format_action, format_cell, parse_action, Action, Objective, Piece, RuleSet, State,
+    WinningStatus,
+};
+use crate::render::{render, RenderOptions};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
 
 pub type ActionFn = fn(state: &State) -> (Option<(usize, usize)>, Option<Piece>);
 
-pub fn play_game(player_1_action_fn: ActionFn, player_2_action_fn: ActionFn) {
-    let mut state = State::new();
-    state.print();
+const HINT_PLAYOUTS: usize = 10000;
 
-    while !state.is_done() {
-        {
-            println!("1p ----------------------------------------");
-
-            println!("action:");
-            let (action, piece) = player_1_action_fn(&state);
-            if let Some((h, w)) = action {
-                state.put_piece(h, w);
-                println!("\tput: ({}, {})", h, w);
+pub const PLAYER_NAMES: [&str; 2] = ["1p", "2p"];
+
+pub enum Decision {
+    Act(Action),
+    Undo,
+    Save(String),
+    Resign,
+    // The player could not produce an action, e.g. an external engine crashed or ran out of time.
+    Forfeit(String),
+}
+
+pub trait Player {
+    fn decide(&mut self, state: &State) -> Decision;
+
+    fn new_game(&mut self) {}
+}
+
+impl Player for ActionFn {
+    fn decide(&mut self, state: &State) -> Decision {
+        Decision::Act(self(state))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameRecord {
+    pub actions: Vec<Action>,
+    pub resigned: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rules: RuleSet,
+}
+
+impl GameRecord {
+    pub fn new() -> Self {
+        GameRecord::default()
+    }
+
+    pub fn final_state(&self) -> State {
+        let mut state = State::new().with_rules(self.rules);
+        for &action in &self.actions {
+            state.apply_action(action);
+        }
+        state
+    }
+
+    // A header line, a `rules <variant>` line for games not played under the classic rules, one
+    // action per line in `format_action` notation, and a final `resign 1p` or `resign 2p` line
+    // when the game ended by resignation. Anything after `#` on a line is a comment.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "# quarto game v1")?;
+        if self.rules != RuleSet::default() {
+            writeln!(writer, "rules {}", self.rules)?;
+        }
+        for &action in &self.actions {
+            writeln!(writer, "{}", format_action(action))?;
+        }
+        if let Some(player) = self.resigned {
+            writeln!(writer, "resign {}", PLAYER_NAMES[player])?;
+        }
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut record = GameRecord::new();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
             }
-            if state.is_done() {
-                break;
+            if let Some(player) = line.strip_prefix("resign ") {
+                record.resigned = PLAYER_NAMES.iter().position(|&name| name == player);
+                continue;
+            }
+            if let Some(rules) = line.strip_prefix("rules ") {
+                record.rules = rules
+                    .parse()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                continue;
             }
-            if let Some(piece) = piece {
-                state.select_piece(piece);
-                println!("\tselect: {}", piece);
+            match parse_action(line) {
+                Some(action) => record.actions.push(action),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid action: {}", line),
+                    ))
+                }
             }
-            println!();
-            state.print();
         }
-        {
-            println!("2p ----------------------------------------");
-
-            println!("action:");
-            let (action, piece) = player_2_action_fn(&state);
-            if let Some((h, w)) = action {
-                state.put_piece(h, w);
-                println!("\tput: ({}, {})", h, w);
+        // Actions are checked once the rules are known, so that replaying the record cannot panic.
+        let mut state = State::new().with_rules(record.rules);
+        for (ply, &action) in record.actions.iter().enumerate() {
+            if !state.is_legal_action(action) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "illegal action at ply {}: {}",
+                        ply + 1,
+                        format_action(action)
+                    ),
+                ));
             }
-            if state.is_done() {
+            state.apply_action(action);
+        }
+        Ok(record)
+    }
+}
+
+/// Every position of a recorded game in turn, each after the action that led to it and drawn like
+/// `State::print` draws boards, for `quarto replay`.
+pub fn replay(record: &GameRecord) -> String {
+    let options = RenderOptions::detect();
+    let mut state = State::new().with_rules(record.rules);
+    let mut replay = render(&state, &options);
+    for (ply, &action) in record.actions.iter().enumerate() {
+        let mover = if state.is_first_player() { 0 } else { 1 };
+        state.apply_action(action);
+        replay.push_str(&format!(
+            "\n{}. {}: {}\n",
+            ply + 1,
+            PLAYER_NAMES[mover],
+            format_action(action)
+        ));
+        if let Some(piece) = state.selected_piece() {
+            replay.push_str(&format!("in hand: {}\n", piece));
+        }
+        replay.push_str(&render(&state, &options));
+    }
+    if let Some(player) = record.resigned {
+        replay.push_str(&format!("\n{} resigned\n", PLAYER_NAMES[player]));
+    }
+    replay
+}
+
+pub fn play_game(player_1_action_fn: ActionFn, player_2_action_fn: ActionFn) -> GameRecord {
+    let mut player_1_action_fn = player_1_action_fn;
+    let mut player_2_action_fn = player_2_action_fn;
+    play_game_with_players(&mut player_1_action_fn, &mut player_2_action_fn)
+}
+
+pub fn play_game_with_players(player_1: &mut dyn Player, player_2: &mut dyn Player) -> GameRecord {
+    play_game_with_rules(player_1, player_2, RuleSet::default())
+}
+
+pub fn play_game_with_rules(
+    player_1: &mut dyn Player,
+    player_2: &mut dyn Player,
+    rules: RuleSet,
+) -> GameRecord {
+    let players: [&mut dyn Player; 2] = [player_1, player_2];
+    let mut record = GameRecord {
+        rules,
+        ..GameRecord::new()
+    };
+    let mut state = record.final_state();
+    state.print();
+
+    while !state.is_done() {
+        let mover = record.actions.len() % 2;
+        println!(
+            "{} ----------------------------------------",
+            PLAYER_NAMES[mover]
+        );
+
+        println!("action:");
+        match players[mover].decide(&state) {
+            Decision::Act((action, piece)) => {
+                record.actions.push((action, piece));
+                state.apply_action((action, piece));
+                match action {
+                    Some((h, w)) => println!("\tput: ({}, {})", h, w),
+                    None if piece.is_none() => println!("\tquarto!"),
+                    None => {}
+                }
+                if state.is_done() {
+                    break;
+                }
+                if let Some(piece) = piece {
+                    println!("\tselect: {}", piece);
+                }
+                println!();
+                state.print();
+            }
+            Decision::Undo => {
+                if record.actions.len() < 2 {
+                    println!("nothing to undo");
+                    continue;
+                }
+                record.actions.truncate(record.actions.len() - 2);
+                state = record.final_state();
+                println!("\tundo");
+                println!();
+                state.print();
+            }
+            Decision::Save(path) => match record.save(&path) {
+                Ok(()) => println!("saved to {}", path),
+                Err(e) => println!("failed to save {}: {}", path, e),
+            },
+            Decision::Resign => {
+                record.resigned = Some(mover);
                 break;
             }
-            if let Some(piece) = piece {
-                state.select_piece(piece);
-                println!("\tselect: {}", piece);
+            Decision::Forfeit(reason) => {
+                println!("{} forfeits: {}", PLAYER_NAMES[mover], reason);
+                record.resigned = Some(mover);
+                break;
             }
-            println!();
-            state.print();
         }
     }
     println!();
     state.print();
 
+    if let Some(player) = record.resigned {
+        println!("{} resigned", PLAYER_NAMES[player]);
+        println!("winner: {}", PLAYER_NAMES[player ^ 1]);
+        return record;
+    }
+    // The player who ended the game is still the active one.
+    let mover = if state.is_first_player() { 0 } else { 1 };
     match state.get_winning_status() {
-        WinningStatus::WIN => println!(
-            "winner: {}",
-            if state.is_first_player() { "1p" } else { "2p" }
-        ),
+        WinningStatus::WIN => println!("winner: {}", PLAYER_NAMES[mover]),
+        WinningStatus::LOSE => println!("winner: {}", PLAYER_NAMES[mover ^ 1]),
         WinningStatus::DRAW => println!("DRAW"),
-        _ => panic!("unreachable code"),
+        WinningStatus::NONE => panic!("unreachable code"),
+    }
+    if state.rules().objective == Objective::Scored && state.can_win() {
+        println!("points: {}", state.line_score());
     }
+    record
 }
 
-pub fn human_action(state: &State) -> (Option<(usize, usize)>, Option<Piece>) {
-    let mut put: Option<(usize, usize)> = None;
-    if !state.is_first_turn() {
+pub struct HumanPlayer {
+    pub coach: bool,
+}
+
+impl HumanPlayer {
+    fn read_command(&self, state: &State) -> Option<Command> {
         loop {
-            println!("Input put action: (h, w)");
-            println!(
-                "Example\t: input: {} {}",
-                state.legal_placements()[0].0,
-                state.legal_placements()[0].1
-            );
             let mut input = String::new();
-            std::io::stdin().read_line(&mut input).unwrap();
-            let mut iter = input.split_whitespace();
-            let h = match iter.next() {
-                Some(x) => x.parse::<usize>().or(Err(())),
-                None => Err(()),
-            };
-            let w = match iter.next() {
-                Some(x) => x.parse::<usize>().or(Err(())),
-                None => Err(()),
-            };
-            if h.is_err() || w.is_err() {
-                println!("input error");
-                continue;
+            match io::stdin().read_line(&mut input) {
+                Ok(0) | Err(_) => return None,
+                Ok(_) => {}
             }
-            let h = h.unwrap();
-            let w = w.unwrap();
-            if state.legal_placements().contains(&(h, w)) {
-                put = Some((h, w));
-                break;
-            } else {
-                println!("illegal action");
+            match parse_command(&input, state) {
+                Ok(command) => return Some(command),
+                Err(e) => println!("input error: {}", e),
             }
         }
     }
-    let mut select: Option<Piece> = None;
-    if (put.is_none() || !state.can_put_then_win(put.unwrap().0, put.unwrap().1))
-        && !state.is_last_turn()
-    {
-        loop {
-            println!("Input select action: (piece)");
-            println!("Example\t: input: {}", state.legal_pieces()[0].to_string());
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input).unwrap();
-            let piece = input.trim().parse::<Piece>();
-            if piece.is_err() {
-                println!("input error");
-                continue;
-            }
-            let piece = piece.unwrap();
-            if state.legal_pieces().contains(&&piece) {
-                select = Some(piece);
-                break;
-            } else {
-                println!("illegal action");
-            }
+
+    // Shows the coach's warnings and returns whether the human keeps the action.
+    fn keep_after_review(&self, state: &State, action: Action) -> bool {
+        let warnings = review(state, action);
+        if warnings.is_empty() {
+            return true;
         }
+        for warning in &warnings {
+            println!("coach: {}", warning);
+        }
+        println!("Take it back? (y/N)");
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) | Err(_) => true,
+            Ok(_) => !input.trim().eq_ignore_ascii_case("y"),
+        }
+    }
+
+    fn hint(&self, state: &State) {
+        let (place, piece) = mcts_action(state, HINT_PLAYOUTS);
+        println!("hint: {}", format_action((place, piece)));
     }
-    (put, select)
 }
 
-pub fn test_first_player_win_rate(
-    game_number: i32,
-    ai_pairs: ((&str, ActionFn), (&str, ActionFn)),
-) {
-    let mut first_player_win_rate = 0.0;
-    for i in 0..game_number {
-        let base_state = State::new();
-        for j in 0..2 {
-            let mut state = base_state;
-            let first_ai;
-            let second_ai;
-            if j == 0 {
-                first_ai = ai_pairs.0;
-                second_ai = ai_pairs.1
+impl Player for HumanPlayer {
+    fn decide(&mut self, state: &State) -> Decision {
+        let mut put: Option<(usize, usize)> = None;
+        let mut select: Option<Piece> = None;
+        let needs_put = !state.is_first_turn();
+        // Under the call rule a win has to be called, so it is not spotted for the human.
+        let calling = state.rules().quarto_call;
+        loop {
+            let needs_select = !state.is_last_turn()
+                && (calling || put.is_none_or(|(h, w)| !state.can_put_then_win(h, w)));
+            if put.is_some() || !needs_put {
+                if select.is_some() || !needs_select {
+                    if !self.coach || self.keep_after_review(state, (put, select)) {
+                        return Decision::Act((put, select));
+                    }
+                    put = None;
+                    select = None;
+                    continue;
+                }
+                println!("Input select action: (piece)");
+                println!(
+                    "Example\t: input: {}  or  white circle",
+                    state.legal_pieces()[0]
+                );
             } else {
-                first_ai = ai_pairs.1;
-                second_ai = ai_pairs.0
+                println!("Input put action: (cell)");
+                println!(
+                    "Example\t: input: {}  or  {} {}",
+                    format_cell(state.legal_placements()[0]),
+                    state.legal_placements()[0].0,
+                    state.legal_placements()[0].1
+                );
             }
-            let mut active_ai = first_ai;
-            loop {
-                let (action, piece) = active_ai.1(&state);
-                if let Some((h, w)) = action {
-                    state.put_piece(h, w);
+
+            let command = match self.read_command(state) {
+                Some(command) => command,
+                None => {
+                    println!("end of input");
+                    return Decision::Resign;
                 }
-                if state.is_done() {
-                    match state.get_winning_status() {
-                        WinningStatus::WIN => println!("{} win!!", active_ai.0),
-                        WinningStatus::DRAW => println!("draw"),
-                        _ => panic!("unreachable code"),
+            };
+            match command {
+                Command::Move { place, piece } => {
+                    if let Some(place) = place {
+                        if !needs_put {
+                            println!("illegal action: there is nothing to place on the first turn");
+                            continue;
+                        }
+                        if put.is_some() {
+                            println!("illegal action: the piece is already placed");
+                            continue;
+                        }
+                        if !state.legal_placements().contains(&place) {
+                            println!("illegal action");
+                            continue;
+                        }
+                        put = Some(place);
+                    } else if needs_put && put.is_none() {
+                        println!("illegal action: place the piece in hand first");
+                        continue;
+                    }
+                    if let Some(piece) = piece {
+                        select = Some(piece);
                     }
-                    break;
                 }
-                if let Some(piece) = piece {
-                    state.select_piece(piece);
+                Command::Quarto if !calling => println!("quarto needs the call rule"),
+                Command::Quarto => match put {
+                    None if state.can_claim() => return Decision::Act((None, None)),
+                    None => println!("there is no open line to claim"),
+                    Some((h, w)) if state.can_put_then_win(h, w) => {
+                        return Decision::Act((put, None))
+                    }
+                    Some(_) => println!("the placement does not complete a line"),
+                },
+                Command::Undo if put.is_some() => put = None,
+                Command::Undo => return Decision::Undo,
+                Command::Hint => self.hint(state),
+                Command::Save(path) => {
+                    return Decision::Save(path.unwrap_or_else(|| String::from("game.txt")))
                 }
-                active_ai = if active_ai == first_ai {
-                    second_ai
-                } else {
-                    first_ai
-                };
-            }
-
-            let mut win_rate_point = state.get_first_player_score_for_win_rate();
-            if j == 1 {
-                win_rate_point = 1.0 - win_rate_point;
+                Command::Resign => return Decision::Resign,
+                Command::Board => state.print(),
+                Command::Help => println!("{}", HELP),
             }
-            first_player_win_rate += win_rate_point;
+        }
+    }
+}
 
-            state.print();
+/// Asks for an action on stdin, or `None` when the human resigns or the input ends.
+pub fn human_action(state: &State) -> Option<Action> {
+    loop {
+        match (HumanPlayer { coach: false }).decide(state) {
+            Decision::Act(action) => return Some(action),
+            Decision::Resign => return None,
+            _ => println!("undo and save need a game started with HumanPlayer"),
         }
-        println!(
-            "i {}, w {}",
-            i,
-            first_player_win_rate / ((i + 1) * 2) as f64
-        );
     }
-    first_player_win_rate /= (game_number * 2) as f64;
-    println!(
-        "Winning rate of {} to {}:\t{}",
-        ai_pairs.0 .0, ai_pairs.1 .0, first_player_win_rate
-    );
 }
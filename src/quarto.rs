@@ -1,44 +1,103 @@
+//! The rules of Quarto: pieces, positions, actions and their notation.
+
+use crate::render::{render, RenderOptions};
 use std::collections::HashSet;
 use std::fmt;
 use std::fmt::Formatter;
 use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// The most binary attributes a piece can have, so that the unused pieces fit in a `u64`.
+pub const MAX_ATTRIBUTES: usize = 6;
+
+const MAX_PIECES: usize = 1 << MAX_ATTRIBUTES;
 
-const SIZE: usize = 4;
+/// A placement of the piece in hand and the piece handed to the opponent. The first action only
+/// hands over a piece, and a placement that ends the game hands over none.
+pub type Action = (Option<(usize, usize)>, Option<Piece>);
 
-#[derive(Clone, Copy, Hash, Eq, PartialEq)]
+/// A piece with up to `MAX_ATTRIBUTES` binary attributes, one bit each. The four classic
+/// attributes are the color (bit 3), shape, height and top (bit 0), written as their letters, e.g.
+/// `BSTF`. Variants with more attributes append the higher bits as a number, e.g. `BSTF+2`.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 pub struct Piece {
-    color: Color,
-    shape: Shape,
-    height: Height,
-    top: Top,
+    bits: u8,
 }
 
 impl Piece {
+    pub fn new(color: Color, shape: Shape, height: Height, top: Top) -> Piece {
+        let bits = ((color == Color::White) as u8) << 3
+            | ((shape == Shape::Circle) as u8) << 2
+            | ((height == Height::Short) as u8) << 1
+            | (top == Top::Hole) as u8;
+        Piece { bits }
+    }
+
+    pub fn color(&self) -> Color {
+        [Color::Black, Color::White][self.attribute(3) as usize]
+    }
+
+    pub fn shape(&self) -> Shape {
+        [Shape::Square, Shape::Circle][self.attribute(2) as usize]
+    }
+
+    pub fn height(&self) -> Height {
+        [Height::Tall, Height::Short][self.attribute(1) as usize]
+    }
+
+    pub fn top(&self) -> Top {
+        [Top::Flat, Top::Hole][self.attribute(0) as usize]
+    }
+
+    /// The value of attribute `i`, where 0 is the top and 3 the color.
+    pub fn attribute(&self, i: usize) -> bool {
+        self.bits >> i & 1 == 1
+    }
+
     pub fn get_idx(&self) -> (usize, usize, usize, usize) {
         (
-            match self.color {
-                Color::Black => 0,
-                Color::White => 1,
-            },
-            match self.shape {
-                Shape::Square => 0,
-                Shape::Circle => 1,
-            },
-            match self.height {
-                Height::Tall => 0,
-                Height::Short => 1,
-            },
-            match self.top {
-                Top::Flat => 0,
-                Top::Hole => 1,
-            },
+            self.attribute(3) as usize,
+            self.attribute(2) as usize,
+            self.attribute(1) as usize,
+            self.attribute(0) as usize,
         )
     }
+
+    pub fn attribute_names(&self) -> [&'static str; 4] {
+        let (idx0, idx1, idx2, idx3) = self.get_idx();
+        [
+            ["black", "white"][idx0],
+            ["square", "circle"][idx1],
+            ["tall", "short"][idx2],
+            ["flat", "hole"][idx3],
+        ]
+    }
+
+    /// The attribute bits as a number in `0..2^k`.
+    pub fn to_index(self) -> usize {
+        self.bits as usize
+    }
+
+    pub fn from_index(idx: usize) -> Piece {
+        assert!(idx < MAX_PIECES, "piece index out of range: {}", idx);
+        Piece { bits: idx as u8 }
+    }
 }
 
 impl fmt::Display for Piece {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}{}{}{}", self.color, self.shape, self.height, self.top)
+        write!(
+            f,
+            "{}{}{}{}",
+            self.color(),
+            self.shape(),
+            self.height(),
+            self.top()
+        )?;
+        if self.bits >> 4 != 0 {
+            write!(f, "+{}", self.bits >> 4)?;
+        }
+        Ok(())
     }
 }
 
@@ -46,6 +105,16 @@ impl FromStr for Piece {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (s, high) = match s.split_once('+') {
+            Some((code, high)) if high.bytes().all(|b| b.is_ascii_digit()) => {
+                match high.parse::<usize>() {
+                    Ok(high) if high > 0 && high < MAX_PIECES >> 4 => (code, high),
+                    _ => return Err(()),
+                }
+            }
+            Some(_) => return Err(()),
+            None => (s, 0),
+        };
         if s.len() != 4 {
             return Err(());
         }
@@ -76,17 +145,55 @@ impl FromStr for Piece {
             'H' => Top::Hole,
             _ => return Err(()),
         };
-        Ok(Piece {
-            color,
-            shape,
-            height,
-            top,
-        })
+        Ok(Piece::from_index(
+            high << 4 | Piece::new(color, shape, height, top).to_index(),
+        ))
+    }
+}
+
+/// Cells are written as a column letter and a 1-based row, so `a1` is (0, 0) and `d2` is (1, 3).
+pub fn format_cell((h, w): (usize, usize)) -> String {
+    format!("{}{}", (b'a' + w as u8) as char, h + 1)
+}
+
+pub fn parse_cell(s: &str) -> Option<(usize, usize)> {
+    let mut chars = s.chars();
+    let column = chars.next()?.to_ascii_lowercase();
+    let row = chars.as_str().parse::<usize>().ok()?;
+    if !column.is_ascii_lowercase() || !(1..=26).contains(&row) {
+        return None;
+    }
+    Some((row - 1, column as usize - 'a' as usize))
+}
+
+/// An action is written as `<cell> <piece>` with `-` for a missing part, e.g. `b3 WCSH`, `- BSTF`.
+pub fn format_action((place, piece): Action) -> String {
+    format!(
+        "{} {}",
+        place.map_or(String::from("-"), format_cell),
+        piece.map_or(String::from("-"), |piece| piece.to_string())
+    )
+}
+
+pub fn parse_action(s: &str) -> Option<Action> {
+    let mut tokens = s.split_whitespace();
+    let place = match tokens.next()? {
+        "-" => None,
+        cell => Some(parse_cell(cell)?),
+    };
+    let piece = match tokens.next()? {
+        "-" => None,
+        piece => Some(piece.parse::<Piece>().ok()?),
+    };
+    if tokens.next().is_some() {
+        return None;
     }
+    Some((place, piece))
 }
 
-#[derive(Clone, Copy, Hash, Eq, PartialEq)]
-enum Color {
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Color {
     Black,
     White,
 }
@@ -100,8 +207,9 @@ impl fmt::Display for Color {
     }
 }
 
-#[derive(Clone, Copy, Hash, Eq, PartialEq)]
-enum Shape {
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Shape {
     Square,
     Circle,
 }
@@ -115,8 +223,9 @@ impl fmt::Display for Shape {
     }
 }
 
-#[derive(Clone, Copy, Hash, Eq, PartialEq)]
-enum Height {
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Height {
     Tall,
     Short,
 }
@@ -130,8 +239,9 @@ impl fmt::Display for Height {
     }
 }
 
-#[derive(Clone, Copy, Hash, Eq, PartialEq)]
-enum Top {
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Top {
     Flat,
     Hole,
 }
@@ -145,30 +255,158 @@ impl fmt::Display for Top {
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct State {
+/// Which 2×2 blocks of pieces also win.
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Squares {
+    #[default]
+    Off,
+    Blocks,
+    // Blocks may also wrap around the edges of the board.
+    Toroidal,
+}
+
+/// What completing a line is worth to its player.
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Objective {
+    #[default]
+    Win,
+    // Completing a line loses.
+    Misere,
+    // Completing a line wins a point for each attribute its pieces share.
+    Scored,
+}
+
+/// The rule variants a game is played with. The default is the classic game.
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RuleSet {
+    pub squares: Squares,
+    // A completed line only wins when its player calls "Quarto!" by handing over no piece. A line
+    // left uncalled can be claimed by the opponent with `(None, None)` before they place.
+    pub quarto_call: bool,
+    pub objective: Objective,
+}
+
+impl RuleSet {
+    /// Whether the variants can be combined. Under misère a line loses for its player, so nobody
+    /// would call or claim one and the call rule has no effect but to let lines go unpunished.
+    pub fn is_playable(&self) -> bool {
+        !(self.quarto_call && self.objective == Objective::Misere)
+    }
+}
+
+// Written as `classic` or the variants joined by commas, e.g. `squares,call`.
+impl fmt::Display for RuleSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut variants = Vec::new();
+        match self.squares {
+            Squares::Off => {}
+            Squares::Blocks => variants.push("squares"),
+            Squares::Toroidal => variants.push("toroidal"),
+        }
+        if self.quarto_call {
+            variants.push("call");
+        }
+        match self.objective {
+            Objective::Win => {}
+            Objective::Misere => variants.push("misere"),
+            Objective::Scored => variants.push("scored"),
+        }
+        if variants.is_empty() {
+            variants.push("classic");
+        }
+        write!(f, "{}", variants.join(","))
+    }
+}
+
+impl FromStr for RuleSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = RuleSet::default();
+        for variant in s.split(',') {
+            match variant.trim() {
+                "classic" => {}
+                "squares" => rules.squares = Squares::Blocks,
+                "toroidal" => rules.squares = Squares::Toroidal,
+                "call" => rules.quarto_call = true,
+                "misere" => rules.objective = Objective::Misere,
+                "scored" => rules.objective = Objective::Scored,
+                variant => return Err(format!("unknown rule variant: {}", variant)),
+            }
+        }
+        if !rules.is_playable() {
+            return Err(String::from("misere cannot be played with the call rule"));
+        }
+        Ok(rules)
+    }
+}
+
+/// A position: the board, the unused pieces and the piece the player to move must place. The game
+/// is played on an `N`×`N` board with the `2^K` pieces of `K` attributes, so smaller variants such
+/// as `GameState<3, 3>` can be solved exactly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameState<const N: usize, const K: usize> {
     turn: usize,
-    unused_pieces: [[[[bool; 2]; 2]; 2]; 2],
-    board: [[Option<Piece>; SIZE]; SIZE],
+    // Bit `Piece::to_index` is set for every piece that has not been selected.
+    unused_pieces: u64,
+    board: [[Option<Piece>; N]; N],
     active_player: usize,
     selected_piece: Option<Piece>,
+    rules: RuleSet,
+    // The last placement or claim won the game.
+    won: bool,
+    // The last placement completed a line without calling it.
+    open_line: bool,
 }
 
-impl State {
+/// The classic game on a 4×4 board with four attributes.
+pub type State = GameState<4, 4>;
+
+impl<const N: usize, const K: usize> Default for GameState<N, K> {
+    fn default() -> Self {
+        GameState::new()
+    }
+}
+
+impl<const N: usize, const K: usize> GameState<N, K> {
+    pub const SIZE: usize = N;
+
+    pub const PIECE_NUMBER: usize = 1 << K;
+
+    const ATTRIBUTE_MASK: u8 = ((1u16 << K) - 1) as u8;
+
     pub fn new() -> Self {
-        State {
+        const { assert!(N >= 2 && N <= 26 && K >= 1 && K <= MAX_ATTRIBUTES) };
+        GameState {
             turn: 0,
-            unused_pieces: [[[[true; 2]; 2]; 2]; 2],
-            board: [[None; SIZE]; SIZE],
+            unused_pieces: u64::MAX >> (64 - Self::PIECE_NUMBER),
+            board: [[None; N]; N],
             active_player: 0,
             selected_piece: None,
+            rules: RuleSet::default(),
+            won: false,
+            open_line: false,
         }
     }
 
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        assert!(rules.is_playable(), "unplayable rules: {}", rules);
+        self.rules = rules;
+        self.won = self.can_win_board(&self.board);
+        self
+    }
+
+    pub fn rules(&self) -> RuleSet {
+        self.rules
+    }
+
     pub fn legal_placements(&self) -> Vec<(usize, usize)> {
         let mut placements = Vec::new();
-        for h in 0..SIZE {
-            for w in 0..SIZE {
+        for h in 0..N {
+            for w in 0..N {
                 if self.board[h][w].is_none() {
                     placements.push((h, w));
                 }
@@ -177,147 +415,514 @@ impl State {
         placements
     }
 
+    pub fn get_piece(&self, h: usize, w: usize) -> Option<Piece> {
+        self.board[h][w]
+    }
+
+    pub fn selected_piece(&self) -> Option<Piece> {
+        self.selected_piece
+    }
+
+    pub fn turn(&self) -> usize {
+        self.turn
+    }
+
+    pub fn empty_count(&self) -> usize {
+        self.board
+            .iter()
+            .flatten()
+            .filter(|cell| cell.is_none())
+            .count()
+    }
+
     pub fn is_first_turn(&self) -> bool {
         self.turn == 0
     }
 
+    // The placement ends the game because the board fills up or no piece is left to hand over.
     pub fn is_last_turn(&self) -> bool {
-        self.turn == 16
+        self.turn > 0 && (self.unused_pieces == 0 || self.empty_count() == 1)
     }
 
     pub fn legal_pieces(&self) -> Vec<Piece> {
-        let mut pieces = Vec::new();
-        for (idx0, &c) in [Color::Black, Color::White].iter().enumerate() {
-            for (idx1, &s) in [Shape::Square, Shape::Circle].iter().enumerate() {
-                for (idx2, &h) in [Height::Tall, Height::Short].iter().enumerate() {
-                    for (idx3, &t) in [Top::Flat, Top::Hole].iter().enumerate() {
-                        if self.unused_pieces[idx0][idx1][idx2][idx3] {
-                            pieces.push(Piece {
-                                color: c,
-                                shape: s,
-                                height: h,
-                                top: t,
-                            });
-                        }
-                    }
-                }
-            }
-        }
-        pieces
+        (0..Self::PIECE_NUMBER)
+            .filter(|idx| self.unused_pieces >> idx & 1 == 1)
+            .map(Piece::from_index)
+            .collect()
     }
 
+    // Placing a piece that completes a line wins, so engines that place and select directly never
+    // leave a line open.
     pub fn put_piece(&mut self, h: usize, w: usize) {
+        self.won = self.can_put_then_win(h, w);
+        self.open_line = false;
         self.board[h][w] = self.selected_piece;
         self.selected_piece = None;
     }
 
     pub fn select_piece(&mut self, piece: Piece) {
-        let (idx0, idx1, idx2, idx3) = piece.get_idx();
-        self.unused_pieces[idx0][idx1][idx2][idx3] = false;
+        self.unused_pieces &= !(1 << piece.to_index());
         self.selected_piece = Some(piece);
         self.turn += 1;
-        self.active_player = self.active_player ^ 1;
+        self.active_player ^= 1;
+    }
+
+    pub fn apply_action(&mut self, (place, piece): Action) {
+        if place.is_none() && piece.is_none() {
+            self.won = self.can_claim();
+            self.open_line = false;
+            return;
+        }
+        if let Some((h, w)) = place {
+            self.put_piece(h, w);
+            if self.won && piece.is_some() && self.rules.quarto_call {
+                self.won = false;
+                self.open_line = true;
+            }
+        }
+        if self.is_done() {
+            return;
+        }
+        if let Some(piece) = piece {
+            self.select_piece(piece);
+        }
+    }
+
+    /// Whether the opponent's last placement left a line open to claim.
+    pub fn can_claim(&self) -> bool {
+        self.open_line
+    }
+
+    /// Every legal action, or none once the game is over.
+    pub fn legal_actions(&self) -> Vec<Action> {
+        if self.is_done() {
+            return Vec::new();
+        }
+        if self.is_first_turn() {
+            return self
+                .legal_pieces()
+                .into_iter()
+                .map(|piece| (None, Some(piece)))
+                .collect();
+        }
+        let mut actions = Vec::new();
+        if self.can_claim() {
+            actions.push((None, None));
+        }
+        for (h, w) in self.legal_placements() {
+            let (must_call, may_call) = self.calls(h, w);
+            if may_call {
+                actions.push((Some((h, w)), None));
+            }
+            if must_call {
+                continue;
+            }
+            for piece in self.legal_pieces() {
+                actions.push((Some((h, w)), Some(piece)));
+            }
+        }
+        actions
+    }
+
+    // Whether placing on (h, w) must end the game, and whether it may. With the call rule a
+    // completed line can be missed by handing over a piece anyway.
+    fn calls(&self, h: usize, w: usize) -> (bool, bool) {
+        let wins = self.can_put_then_win(h, w);
+        (
+            self.is_last_turn() || (wins && !self.rules.quarto_call),
+            self.is_last_turn() || wins,
+        )
+    }
+
+    /// Engines may name a piece together with a placement that ends the game. `apply_action`
+    /// ignores it, but it is not part of a legal action.
+    pub fn normalize_action(&self, (place, piece): Action) -> Action {
+        match place {
+            Some((h, w)) if h < N && w < N && self.board[h][w].is_none() && self.calls(h, w).0 => {
+                (place, None)
+            }
+            _ => (place, piece),
+        }
+    }
+
+    pub fn is_legal_action(&self, (place, piece): Action) -> bool {
+        if self.is_done() {
+            return false;
+        }
+        if place.is_none() && piece.is_none() {
+            return self.can_claim();
+        }
+        let (must_call, may_call) = match place {
+            None if self.is_first_turn() => (false, false),
+            Some((h, w)) if !self.is_first_turn() && h < N && w < N => {
+                if self.board[h][w].is_some() {
+                    return false;
+                }
+                self.calls(h, w)
+            }
+            _ => return false,
+        };
+        match piece {
+            None => may_call,
+            Some(piece) => !must_call && self.legal_pieces().contains(&piece),
+        }
+    }
+
+    /// Rows from the top separated by `/`, each cell a piece code or `.`, then the piece in hand or
+    /// `-`, e.g. `BSSF.../..../..WCTH./.... BCTF`. Games not played under the classic rules add
+    /// the rules, and under the call rule `won` for a called or claimed win and `open` for a line
+    /// left open to claim, e.g. `BSSFBSSHBSTFBSTH/..../..../.... WCTF call open`.
+    pub fn notation(&self) -> String {
+        let rows: Vec<String> = self
+            .board
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| cell.map_or(String::from("."), |piece| piece.to_string()))
+                    .collect()
+            })
+            .collect();
+        let mut notation = format!(
+            "{} {}",
+            rows.join("/"),
+            self.selected_piece
+                .map_or(String::from("-"), |piece| piece.to_string())
+        );
+        if self.rules != RuleSet::default() {
+            notation.push_str(&format!(" {}", self.rules));
+        }
+        if self.rules.quarto_call && self.won {
+            notation.push_str(" won");
+        } else if self.open_line {
+            notation.push_str(" open");
+        }
+        notation
     }
 
+    pub fn from_notation(s: &str) -> Option<Self> {
+        let mut tokens = s.split_whitespace().peekable();
+        let rows: Vec<&str> = tokens.next()?.split('/').collect();
+        let selected = match tokens.next()? {
+            "-" => None,
+            piece => Some(piece.parse::<Piece>().ok()?),
+        };
+        let rules = match tokens.peek() {
+            Some(&"won" | &"open") | None => RuleSet::default(),
+            Some(rules) => {
+                let rules = rules.parse().ok()?;
+                tokens.next();
+                rules
+            }
+        };
+        let (won, open_line) = match tokens.next() {
+            None => (false, false),
+            Some("won") => (true, false),
+            Some("open") => (false, true),
+            Some(_) => return None,
+        };
+        if rows.len() != N || tokens.next().is_some() {
+            return None;
+        }
+
+        let mut board = [[None; N]; N];
+        for (h, row) in rows.iter().enumerate() {
+            let mut rest = *row;
+            for cell in board[h].iter_mut() {
+                if let Some(tail) = rest.strip_prefix('.') {
+                    rest = tail;
+                    continue;
+                }
+                // Four letters, then `+` and the higher attributes if there are any.
+                let mut len = 4;
+                if rest.get(len..)?.starts_with('+') {
+                    len += 1 + rest[len + 1..]
+                        .bytes()
+                        .take_while(u8::is_ascii_digit)
+                        .count();
+                }
+                *cell = Some(rest[..len].parse::<Piece>().ok()?);
+                rest = &rest[len..];
+            }
+            if !rest.is_empty() {
+                return None;
+            }
+        }
+        if !rules.quarto_call {
+            // Without the call rule the board tells whether the game was won.
+            if won || open_line {
+                return None;
+            }
+            let won = Self::new().with_rules(rules).can_win_board(&board);
+            return GameState::from_board_with_rules(board, selected, rules, won, false);
+        }
+        GameState::from_board_with_rules(board, selected, rules, won, open_line)
+    }
+
+    /// `None` unless every piece is used at most once and the position is reachable under the
+    /// classic rules.
+    pub fn from_board(board: [[Option<Piece>; N]; N], selected: Option<Piece>) -> Option<Self> {
+        let won = Self::new().can_win_board(&board);
+        GameState::from_board_with_rules(board, selected, RuleSet::default(), won, false)
+    }
+
+    /// Like `from_board` under `rules`, with whether the last placement or claim won the game and
+    /// whether the last placement left a line open to claim. `None` for unplayable rules or if they
+    /// do not fit the board: without the call rule a game is won exactly when the board has a line,
+    /// and with it a line can be left open only with a piece in hand.
+    pub fn from_board_with_rules(
+        board: [[Option<Piece>; N]; N],
+        selected: Option<Piece>,
+        rules: RuleSet,
+        won: bool,
+        open_line: bool,
+    ) -> Option<Self> {
+        if !rules.is_playable() {
+            return None;
+        }
+        let mut state = GameState::new().with_rules(rules);
+        state.board = board;
+        let line = state.can_win_board(&board);
+        let fits = if rules.quarto_call {
+            (line || !won && !open_line) && !(open_line && (won || selected.is_none()))
+        } else {
+            won == line && !open_line
+        };
+        if !fits {
+            return None;
+        }
+        state.won = won;
+        state.open_line = open_line;
+        let pieces: Vec<Piece> = board
+            .iter()
+            .flatten()
+            .flatten()
+            .copied()
+            .chain(selected)
+            .collect();
+        for &piece in &pieces {
+            let idx = piece.to_index();
+            if idx >= Self::PIECE_NUMBER || state.unused_pieces >> idx & 1 == 0 {
+                return None;
+            }
+            state.unused_pieces &= !(1 << idx);
+        }
+        // Without a piece in hand only the initial position and finished games are reachable.
+        if selected.is_none() && !pieces.is_empty() && !state.is_done() {
+            return None;
+        }
+        state.selected_piece = selected;
+        state.turn = pieces.len();
+        state.active_player = state.turn % 2;
+        Some(state)
+    }
+
+    // Whether the piece in hand completes a line, or a square when the rules count them, through
+    // (h, w).
     pub fn can_put_then_win(&self, h: usize, w: usize) -> bool {
         let mut board = self.board;
         board[h][w] = self.selected_piece;
-        Self::can_win_board(&board)
+        if Self::have_common_attribute(board[h]) {
+            return true;
+        }
+        if Self::have_common_attribute::<N>(std::array::from_fn(|j| board[j][w])) {
+            return true;
+        }
+        if h == w && Self::have_common_attribute::<N>(std::array::from_fn(|j| board[j][j])) {
+            return true;
+        }
+        if h + w == N - 1
+            && Self::have_common_attribute::<N>(std::array::from_fn(|j| board[j][N - 1 - j]))
+        {
+            return true;
+        }
+        self.squares().any(|square| {
+            square.contains(&(h, w))
+                && Self::have_common_attribute(square.map(|(h, w)| board[h][w]))
+        })
     }
 
+    /// Whether the last placement or claim won the game.
     pub fn can_win(&self) -> bool {
-        Self::can_win_board(&self.board)
+        self.won
     }
 
-    fn can_win_board(board: &[[Option<Piece>; SIZE]; SIZE]) -> bool {
-        for i in 0..SIZE {
-            if Self::have_common_attribute([board[i][0], board[i][1], board[i][2], board[i][3]]) {
+    fn can_win_board(&self, board: &[[Option<Piece>; N]; N]) -> bool {
+        for (i, row) in board.iter().enumerate() {
+            if Self::have_common_attribute(*row) {
                 return true;
             }
-            if Self::have_common_attribute([board[0][i], board[1][i], board[2][i], board[3][i]]) {
+            if Self::have_common_attribute::<N>(std::array::from_fn(|j| board[j][i])) {
                 return true;
             }
         }
-        if Self::have_common_attribute([board[0][0], board[1][1], board[2][2], board[3][3]]) {
+        if Self::have_common_attribute::<N>(std::array::from_fn(|j| board[j][j])) {
             return true;
         }
-        if Self::have_common_attribute([board[0][3], board[1][2], board[2][1], board[3][0]]) {
+        if Self::have_common_attribute::<N>(std::array::from_fn(|j| board[j][N - 1 - j])) {
             return true;
         }
+        self.squares()
+            .any(|square| Self::have_common_attribute(square.map(|(h, w)| board[h][w])))
+    }
+
+    // The 2×2 blocks the rules count, each from its top left cell.
+    fn squares(&self) -> impl Iterator<Item = [(usize, usize); 4]> {
+        let corners = match self.rules.squares {
+            Squares::Off => 0,
+            Squares::Blocks => N - 1,
+            Squares::Toroidal => N,
+        };
+        (0..corners * corners).map(move |i| {
+            let (h, w) = (i / corners, i % corners);
+            let (h2, w2) = ((h + 1) % N, (w + 1) % N);
+            [(h, w), (h, w2), (h2, w), (h2, w2)]
+        })
+    }
+
+    /// The rows, the columns and both diagonals.
+    pub fn lines() -> Vec<[(usize, usize); N]> {
+        let mut lines = Vec::new();
+        for i in 0..N {
+            lines.push(std::array::from_fn(|j| (i, j)));
+            lines.push(std::array::from_fn(|j| (j, i)));
+        }
+        lines.push(std::array::from_fn(|j| (j, j)));
+        lines.push(std::array::from_fn(|j| (j, N - 1 - j)));
+        lines
+    }
+
+    /// The lines followed by the squares the rules count.
+    pub fn patterns(&self) -> Vec<Vec<(usize, usize)>> {
+        Self::lines()
+            .into_iter()
+            .map(Vec::from)
+            .chain(self.squares().map(Vec::from))
+            .collect()
+    }
+
+    pub fn winning_lines(&self) -> Vec<Vec<(usize, usize)>> {
+        self.patterns()
+            .into_iter()
+            .filter(|pattern| {
+                let pieces: Vec<Piece> = pattern
+                    .iter()
+                    .filter_map(|&(h, w)| self.board[h][w])
+                    .collect();
+                pieces.len() == pattern.len() && Self::common_attributes(&pieces) != 0
+            })
+            .collect()
+    }
+
+    /// Patterns with one empty square whose pieces already share an attribute.
+    pub fn threatened_lines(&self) -> Vec<Vec<(usize, usize)>> {
+        self.patterns()
+            .into_iter()
+            .filter(|pattern| {
+                let pieces: Vec<Piece> = pattern
+                    .iter()
+                    .filter_map(|&(h, w)| self.board[h][w])
+                    .collect();
+                pieces.len() == pattern.len() - 1 && Self::common_attributes(&pieces) != 0
+            })
+            .collect()
+    }
 
-        false
+    // One bit per attribute on which all the pieces agree.
+    fn common_attributes(pieces: &[Piece]) -> u8 {
+        let mut ones = Self::ATTRIBUTE_MASK;
+        let mut zeros = Self::ATTRIBUTE_MASK;
+        for piece in pieces {
+            ones &= piece.bits;
+            zeros &= !piece.bits;
+        }
+        ones | zeros
     }
 
-    fn have_common_attribute(pieces: [Option<Piece>; SIZE]) -> bool {
-        let mut color = HashSet::new();
-        let mut shape = HashSet::new();
-        let mut height = HashSet::new();
-        let mut top = HashSet::new();
+    fn have_common_attribute<const L: usize>(pieces: [Option<Piece>; L]) -> bool {
+        let mut ones = Self::ATTRIBUTE_MASK;
+        let mut zeros = Self::ATTRIBUTE_MASK;
         for piece in pieces {
-            if piece.is_none() {
+            let Some(piece) = piece else {
                 return false;
-            }
-            let piece = piece.unwrap();
-            color.insert(piece.color);
-            shape.insert(piece.shape);
-            height.insert(piece.height);
-            top.insert(piece.top);
+            };
+            ones &= piece.bits;
+            zeros &= !piece.bits;
         }
-        color.len() == 1 || shape.len() == 1 || height.len() == 1 || top.len() == 1
+        ones | zeros != 0
     }
 
     pub fn is_done(&self) -> bool {
         if self.can_win() {
             return true;
         }
-        for h in 0..SIZE {
-            for w in 0..SIZE {
-                if self.board[h][w].is_none() {
-                    return false;
-                }
-            }
+        // With fewer pieces than cells the game also ends once every piece is placed.
+        if self.selected_piece.is_none() && self.unused_pieces == 0 {
+            return true;
         }
-        true
+        self.board.iter().flatten().all(Option::is_some)
     }
 
+    /// The result for the player who made the last placement or claim.
     pub fn get_winning_status(&self) -> WinningStatus {
         if !self.is_done() {
             return WinningStatus::NONE;
         }
         if self.can_win() {
-            return WinningStatus::WIN;
+            return match self.rules.objective {
+                Objective::Misere => WinningStatus::LOSE,
+                Objective::Win | Objective::Scored => WinningStatus::WIN,
+            };
         }
         WinningStatus::DRAW
     }
 
-    pub fn is_first_player(&self) -> bool {
-        self.active_player == 0
+    /// The most attributes shared by the pieces of a completed line, or 0 without one.
+    pub fn line_score(&self) -> u32 {
+        self.winning_lines()
+            .iter()
+            .map(|line| {
+                let pieces: Vec<Piece> =
+                    line.iter().filter_map(|&(h, w)| self.board[h][w]).collect();
+                Self::common_attributes(&pieces).count_ones()
+            })
+            .max()
+            .unwrap_or(0)
     }
 
-    pub fn get_first_player_score_for_win_rate(&self) -> f64 {
+    /// The result for the player who made the last placement or claim, from 0 for a loss through
+    /// 0.5 for a draw to 1 for a win. Scored wins are worth more the more attributes they share,
+    /// up to 1 when the line shares all `K`.
+    pub fn reward(&self) -> f64 {
         match self.get_winning_status() {
-            WinningStatus::WIN => {
-                if self.is_first_player() {
-                    1.0
-                } else {
-                    0.0
-                }
-            }
-            WinningStatus::LOSE => {
-                if self.is_first_player() {
-                    0.0
-                } else {
-                    1.0
-                }
+            WinningStatus::WIN if self.rules.objective == Objective::Scored => {
+                0.5 + 0.5 * self.line_score() as f64 / K as f64
             }
+            WinningStatus::WIN => 1.0,
+            WinningStatus::LOSE => 0.0,
             _ => 0.5,
         }
     }
 
+    pub fn is_first_player(&self) -> bool {
+        self.active_player == 0
+    }
+
+    /// The first player's [`GameState::reward`]: 1 when they won, 0 when they lost and 0.5 for a
+    /// draw or an unfinished game.
+    pub fn get_first_player_score_for_win_rate(&self) -> f64 {
+        if self.is_first_player() {
+            self.reward()
+        } else {
+            1.0 - self.reward()
+        }
+    }
+}
+
+impl State {
     pub fn print(&self) {
         println!("turn: {}", self.turn);
-        if self.selected_piece.is_some() {
-            println!("selected piece: {}", self.selected_piece.unwrap());
+        if let Some(piece) = self.selected_piece {
+            println!("selected piece: {}", piece);
         }
         let unused_pieces = self.legal_pieces();
         print!("unused pieces: {}\t", unused_pieces.len());
@@ -326,34 +931,333 @@ impl State {
         }
         println!();
         println!();
-        println!("{}", self);
+        println!("{}", render(self, &RenderOptions::detect()));
     }
 }
 
-impl fmt::Display for State {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        writeln!(f, "      0        1        2        3     ")?;
-        for h in 0..SIZE {
-            writeln!(f, "  +--------+--------+--------+--------+")?;
-            write!(f, "{} ", h)?;
-            for w in 0..SIZE {
-                write!(f, "| ")?;
-                match self.board[h][w] {
-                    Some(ref piece) => write!(f, "({})", piece)?,
-                    None => write!(f, "(    )")?,
+// Cells (h * N + w) and pieces (`Piece::to_index`) take K + 1 bits each with 0 for empty, followed
+// by the selected piece. The turn and the unused pieces follow from these.
+impl<const N: usize, const K: usize> GameState<N, K> {
+    fn pack_key(cells: &[[u8; N]; N], selected: u8) -> u128 {
+        const { assert!((N * N + 1) * (K + 1) <= 128) };
+        cells
+            .iter()
+            .flatten()
+            .chain([&selected])
+            .fold(0u128, |key, &cell| key << (K + 1) | cell as u128)
+    }
+
+    pub fn transformed(&self, symmetry: &Symmetry<N, K>) -> Self {
+        let mut state = *self;
+        state.board = [[None; N]; N];
+        for h in 0..N {
+            for w in 0..N {
+                let (th, tw) = symmetry.map_place((h, w));
+                state.board[th][tw] = self.board[h][w].map(|piece| symmetry.map_piece(piece));
+            }
+        }
+        state.selected_piece = self.selected_piece.map(|piece| symmetry.map_piece(piece));
+        state.unused_pieces = 0;
+        for piece in self.legal_pieces() {
+            state.unused_pieces |= 1 << symmetry.map_piece(piece).to_index();
+        }
+        state
+    }
+
+    /// The key of this state as it stands, before any symmetry is applied.
+    pub fn key(&self) -> u128 {
+        let cells = self
+            .board
+            .map(|row| row.map(|cell| cell.map_or(0, |piece| piece.to_index() as u8 + 1)));
+        let selected = self
+            .selected_piece
+            .map_or(0, |piece| piece.to_index() as u8 + 1);
+        Self::pack_key(&cells, selected)
+    }
+
+    /// Returns the smallest key among the images of this state, and a symmetry that maps this
+    /// state onto the state with that key.
+    pub fn canonical(&self, symmetries: &[Symmetry<N, K>]) -> (u128, Symmetry<N, K>) {
+        let mut best_key = u128::MAX;
+        let mut best_symmetry = symmetries[0];
+        for symmetry in symmetries {
+            let mut cells = [[0u8; N]; N];
+            for h in 0..N {
+                for w in 0..N {
+                    if let Some(piece) = self.board[h][w] {
+                        let (th, tw) = symmetry.cells[h][w];
+                        cells[th][tw] = symmetry.pieces[piece.to_index()] + 1;
+                    }
                 }
-                write!(f, " ")?;
             }
-            writeln!(f, "|")?;
+            let selected = self
+                .selected_piece
+                .map_or(0, |piece| symmetry.pieces[piece.to_index()] + 1);
+            let key = Self::pack_key(&cells, selected);
+            if key < best_key {
+                best_key = key;
+                best_symmetry = *symmetry;
+            }
         }
-        writeln!(f, "  +--------+--------+--------+--------+")?;
-        Ok(())
+        (best_key, best_symmetry)
     }
 }
 
+// A relabelling of cells and pieces that keeps the set of lines and the attribute structure.
+// The board part permutes rows and columns with permutations that commute with reversal, which
+// keeps both diagonals, optionally transposed (32 in total on the classic board). The piece part
+// permutes the attributes and flips any of them (384 in total with four attributes).
+#[derive(Clone, Copy)]
+pub struct Symmetry<const N: usize = 4, const K: usize = 4> {
+    cells: [[(usize, usize); N]; N],
+    pieces: [u8; MAX_PIECES],
+}
+
+static BOARD_SYMMETRIES: OnceLock<Vec<Symmetry>> = OnceLock::new();
+
+static ALL_SYMMETRIES: OnceLock<Vec<Symmetry>> = OnceLock::new();
+
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![Vec::new()];
+    }
+    let mut result = Vec::new();
+    for permutation in permutations(n - 1) {
+        for i in 0..n {
+            let mut extended = permutation.clone();
+            extended.insert(i, n - 1);
+            result.push(extended);
+        }
+    }
+    result
+}
+
+impl Symmetry {
+    pub fn board_symmetries() -> &'static [Symmetry] {
+        BOARD_SYMMETRIES.get_or_init(Symmetry::generate_board)
+    }
+
+    pub fn all() -> &'static [Symmetry] {
+        ALL_SYMMETRIES.get_or_init(Symmetry::generate_all)
+    }
+}
+
+impl<const N: usize, const K: usize> Symmetry<N, K> {
+    /// The symmetries that keep every piece, which `board_symmetries` caches for the classic game.
+    pub fn generate_board() -> Vec<Self> {
+        let line_permutations: Vec<Vec<usize>> = permutations(N)
+            .into_iter()
+            .filter(|p| (0..N).all(|i| p[N - 1 - i] == N - 1 - p[i]))
+            .collect();
+        let mut symmetries = Vec::new();
+        for p in &line_permutations {
+            for reverse in [false, true] {
+                for transpose in [false, true] {
+                    let cells = std::array::from_fn(|h| {
+                        std::array::from_fn(|w| {
+                            let th = p[h];
+                            let tw = if reverse { N - 1 - p[w] } else { p[w] };
+                            if transpose {
+                                (tw, th)
+                            } else {
+                                (th, tw)
+                            }
+                        })
+                    });
+                    symmetries.push(Symmetry {
+                        cells,
+                        pieces: std::array::from_fn(|i| i as u8),
+                    });
+                }
+            }
+        }
+        symmetries
+    }
+
+    /// Every symmetry, which `all` caches for the classic game.
+    pub fn generate_all() -> Vec<Self> {
+        let board_symmetries = Self::generate_board();
+        let mut symmetries = Vec::new();
+        for attribute_order in permutations(K) {
+            for flip in 0..GameState::<N, K>::PIECE_NUMBER {
+                let mut pieces = std::array::from_fn(|i| i as u8);
+                for (idx, mapped) in pieces
+                    .iter_mut()
+                    .enumerate()
+                    .take(GameState::<N, K>::PIECE_NUMBER)
+                {
+                    let mut bits = 0;
+                    for (bit, &from) in attribute_order.iter().enumerate() {
+                        bits |= (idx >> from & 1) << bit;
+                    }
+                    *mapped = (bits ^ flip) as u8;
+                }
+                for board_symmetry in &board_symmetries {
+                    symmetries.push(Symmetry {
+                        cells: board_symmetry.cells,
+                        pieces,
+                    });
+                }
+            }
+        }
+        symmetries
+    }
+
+    /// Whether the winning patterns of `rules` map onto each other. Every symmetry keeps the
+    /// lines, but squares are only kept by some.
+    pub fn keeps_patterns(&self, rules: RuleSet) -> bool {
+        let sorted = |pattern: Vec<(usize, usize)>| {
+            let mut pattern = pattern;
+            pattern.sort();
+            pattern
+        };
+        let patterns: HashSet<Vec<(usize, usize)>> = GameState::<N, K>::new()
+            .with_rules(rules)
+            .patterns()
+            .into_iter()
+            .map(sorted)
+            .collect();
+        patterns.iter().all(|pattern| {
+            let mapped = pattern.iter().map(|&place| self.map_place(place)).collect();
+            patterns.contains(&sorted(mapped))
+        })
+    }
+
+    pub fn map_place(&self, (h, w): (usize, usize)) -> (usize, usize) {
+        self.cells[h][w]
+    }
+
+    pub fn unmap_place(&self, place: (usize, usize)) -> (usize, usize) {
+        let cell = self
+            .cells
+            .iter()
+            .flatten()
+            .position(|&c| c == place)
+            .unwrap();
+        (cell / N, cell % N)
+    }
+
+    pub fn map_piece(&self, piece: Piece) -> Piece {
+        Piece::from_index(self.pieces[piece.to_index()] as usize)
+    }
+
+    pub fn unmap_piece(&self, piece: Piece) -> Piece {
+        Piece::from_index(
+            self.pieces
+                .iter()
+                .position(|&p| p as usize == piece.to_index())
+                .unwrap(),
+        )
+    }
+
+    pub fn unmap_action(&self, (place, piece): Action) -> Action {
+        (
+            place.map(|place| self.unmap_place(place)),
+            piece.map(|piece| self.unmap_piece(piece)),
+        )
+    }
+}
+
+impl<const N: usize, const K: usize> fmt::Display for GameState<N, K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", render(self, &RenderOptions::plain()))
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum WinningStatus {
     WIN,
     LOSE,
     DRAW,
     NONE,
 }
+
+// Human readable formats such as JSON use the piece code and the position notation, binary formats
+// use `Piece::to_index` and a byte of `to_index + 1` per cell and for the piece in hand (0 for
+// none), followed by the rules and the state of the last line.
+#[cfg(feature = "serde")]
+mod encoding {
+    use super::{GameState, Piece, RuleSet, MAX_PIECES};
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    impl Serialize for Piece {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                serializer.collect_str(self)
+            } else {
+                serializer.serialize_u8(self.to_index() as u8)
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Piece {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if deserializer.is_human_readable() {
+                let code = String::deserialize(deserializer)?;
+                code.parse()
+                    .map_err(|_| D::Error::custom(format!("invalid piece: {}", code)))
+            } else {
+                match u8::deserialize(deserializer)? as usize {
+                    idx if idx < MAX_PIECES => Ok(Piece::from_index(idx)),
+                    idx => Err(D::Error::custom(format!("invalid piece index: {}", idx))),
+                }
+            }
+        }
+    }
+
+    fn encode_cell(piece: Option<Piece>) -> u8 {
+        piece.map_or(0, |piece| piece.to_index() as u8 + 1)
+    }
+
+    fn decode_cell<E: Error>(byte: u8) -> Result<Option<Piece>, E> {
+        match byte as usize {
+            0 => Ok(None),
+            idx if idx <= MAX_PIECES => Ok(Some(Piece::from_index(idx - 1))),
+            idx => Err(E::custom(format!("invalid cell: {}", idx))),
+        }
+    }
+
+    // Whether the game was won and whether a line was left open, which the board alone does not
+    // tell under the call rule, follow the rules.
+    type Encoded = (Vec<u8>, u8, RuleSet, bool, bool);
+
+    impl<const N: usize, const K: usize> Serialize for GameState<N, K> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                return serializer.serialize_str(&self.notation());
+            }
+            let cells = self.board.iter().flatten().map(|&cell| encode_cell(cell));
+            let encoded: Encoded = (
+                cells.collect(),
+                encode_cell(self.selected_piece),
+                self.rules,
+                self.won,
+                self.open_line,
+            );
+            encoded.serialize(serializer)
+        }
+    }
+
+    impl<'de, const N: usize, const K: usize> Deserialize<'de> for GameState<N, K> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if deserializer.is_human_readable() {
+                let notation = String::deserialize(deserializer)?;
+                return GameState::from_notation(&notation)
+                    .ok_or_else(|| D::Error::custom(format!("invalid position: {}", notation)));
+            }
+            let (cells, selected, rules, won, open_line) = Encoded::deserialize(deserializer)?;
+            if cells.len() != N * N {
+                return Err(D::Error::custom(format!("expected {} cells", N * N)));
+            }
+            let mut board = [[None; N]; N];
+            for (cell, &byte) in board.iter_mut().flatten().zip(&cells) {
+                *cell = decode_cell(byte)?;
+            }
+            let selected = decode_cell(selected)?;
+            GameState::from_board_with_rules(board, selected, rules, won, open_line)
+                .ok_or_else(|| D::Error::custom("unreachable position"))
+        }
+    }
+}
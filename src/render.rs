@@ -0,0 +1,210 @@
+//! Boards drawn as text, with colors, glyphs and highlights of winning and threatened cells
+//! when [`RenderOptions`] allow them.
+
+use crate::quarto::{self, GameState, Height, Piece, Shape, Top};
+use crossterm::style::{Color, Stylize};
+use std::collections::HashSet;
+use std::env;
+use std::io::{self, IsTerminal};
+
+pub struct RenderOptions {
+    pub ansi: bool,
+    pub highlight_winning: bool,
+    pub highlight_threats: bool,
+}
+
+impl RenderOptions {
+    // Colors and highlights only when stdout is a terminal and NO_COLOR is not set, so logs and
+    // pipes keep the plain `Display` output.
+    pub fn detect() -> Self {
+        let ansi = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+        RenderOptions {
+            ansi,
+            highlight_winning: ansi,
+            highlight_threats: ansi,
+        }
+    }
+
+    /// Plain codes without highlights, as `Display` writes them.
+    pub fn plain() -> Self {
+        RenderOptions {
+            ansi: false,
+            highlight_winning: false,
+            highlight_threats: false,
+        }
+    }
+}
+
+// Color by color (black: magenta, white: cyan), glyph by shape (square, circle), filled or hollow
+// by top (flat, hole) and large and bold or small by height (tall, short).
+fn glyph_style(piece: Piece) -> (&'static str, Color, bool) {
+    let glyphs = match (piece.shape(), piece.top()) {
+        (Shape::Square, Top::Flat) => ["■", "▪"],
+        (Shape::Square, Top::Hole) => ["□", "▫"],
+        (Shape::Circle, Top::Flat) => ["●", "•"],
+        (Shape::Circle, Top::Hole) => ["○", "◦"],
+    };
+    let color = match piece.color() {
+        quarto::Color::Black => Color::Magenta,
+        quarto::Color::White => Color::Cyan,
+    };
+    let tall = piece.height() == Height::Tall;
+    (glyphs[usize::from(!tall)], color, tall)
+}
+
+pub fn piece_glyph(piece: Piece) -> String {
+    let (glyph, color, tall) = glyph_style(piece);
+    let glyph = glyph.with(color);
+    if tall {
+        glyph.bold().to_string()
+    } else {
+        glyph.to_string()
+    }
+}
+
+// A six column board cell. The whole cell is one styled span because the reset at the end of a
+// span also clears any background or reverse video set around it.
+pub fn styled_cell(piece: Option<Piece>, background: Option<Color>, reverse: bool) -> String {
+    let mut cell = match piece {
+        Some(piece) => {
+            let (glyph, color, tall) = glyph_style(piece);
+            let cell = format!("  {}   ", glyph).with(color);
+            if tall {
+                cell.bold()
+            } else {
+                cell
+            }
+        }
+        None => String::from("      ").stylize(),
+    };
+    if let Some(background) = background {
+        cell = cell.on(background);
+    }
+    if reverse {
+        cell = cell.reverse();
+    }
+    cell.to_string()
+}
+
+pub fn legend() -> String {
+    format!(
+        "{} black  {} white  ■ square  ● circle  filled: flat  hollow: hole  large: tall  small: short",
+        "■".with(Color::Magenta),
+        "■".with(Color::Cyan)
+    )
+}
+
+pub fn render<const N: usize, const K: usize>(
+    state: &GameState<N, K>,
+    options: &RenderOptions,
+) -> String {
+    let mut winning_cells = HashSet::new();
+    if options.highlight_winning {
+        winning_cells.extend(state.winning_lines().into_iter().flatten());
+    }
+    let mut threatened_cells = HashSet::new();
+    if options.highlight_threats {
+        threatened_cells.extend(state.threatened_lines().into_iter().flatten());
+    }
+
+    let columns: Vec<String> = (0..N).map(|w| w.to_string()).collect();
+    let separator = format!("  +{}", "--------+".repeat(N));
+    let mut lines = Vec::new();
+    lines.push(format!("      {}     ", columns.join("        ")));
+    for h in 0..N {
+        lines.push(separator.clone());
+        let mut line = format!("{} ", h);
+        for w in 0..N {
+            line.push_str("| ");
+            let cell = if options.ansi {
+                let background = if winning_cells.contains(&(h, w)) {
+                    Some(Color::DarkGreen)
+                } else if threatened_cells.contains(&(h, w)) {
+                    Some(Color::DarkYellow)
+                } else {
+                    None
+                };
+                styled_cell(state.get_piece(h, w), background, false)
+            } else {
+                match state.get_piece(h, w) {
+                    Some(piece) if winning_cells.contains(&(h, w)) => format!("*{}*", piece),
+                    Some(piece) => format!("({})", piece),
+                    None if threatened_cells.contains(&(h, w)) => String::from("( !! )"),
+                    None => String::from("(    )"),
+                }
+            };
+            line.push_str(&cell);
+            line.push(' ');
+        }
+        line.push('|');
+        lines.push(line);
+    }
+    lines.push(separator);
+    if options.ansi {
+        lines.push(legend());
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quarto::State;
+
+    fn highlights() -> RenderOptions {
+        RenderOptions {
+            ansi: false,
+            highlight_winning: true,
+            highlight_threats: true,
+        }
+    }
+
+    #[test]
+    fn plain_boards_follow_the_size() {
+        let state = State::from_notation("BSTF.../..../..../.... WCSH").unwrap();
+        let board = render(&state, &RenderOptions::plain());
+        assert_eq!(board, state.to_string());
+        assert_eq!(board.lines().count(), 2 * 4 + 2);
+        assert!(board.contains("0 | (BSTF) | (    ) | (    ) | (    ) |"));
+
+        let small = GameState::<3, 3>::new();
+        let board = render(&small, &RenderOptions::plain());
+        assert_eq!(board.lines().count(), 2 * 3 + 2);
+        assert!(board.starts_with("      0        1        2     \n"));
+        assert!(board.contains("2 | (    ) | (    ) | (    ) |"));
+    }
+
+    #[test]
+    fn lines_are_highlighted() {
+        let threat = State::from_notation("BSTFBSTHBSSF./..../..../.... BCSH").unwrap();
+        let board = render(&threat, &highlights());
+        assert!(board.contains("0 | (BSTF) | (BSTH) | (BSSF) | ( !! ) |"));
+
+        let mut won = threat;
+        won.apply_action((Some((0, 3)), None));
+        let board = render(&won, &highlights());
+        assert!(board.contains("0 | *BSTF* | *BSTH* | *BSSF* | *BCSH* |"));
+        assert!(!render(&won, &RenderOptions::plain()).contains('*'));
+    }
+
+    #[test]
+    fn attributes_have_distinct_styles() {
+        let styles: HashSet<_> = (0..16)
+            .map(|i| {
+                let (glyph, color, tall) = glyph_style(Piece::from_index(i));
+                (glyph, format!("{:?}", color), tall)
+            })
+            .collect();
+        assert_eq!(styles.len(), 16);
+
+        let state = State::from_notation("BSTF.../..../..../.... WCSH").unwrap();
+        let options = RenderOptions {
+            ansi: true,
+            ..highlights()
+        };
+        let board = render(&state, &options);
+        assert!(board.contains('\u{1b}'));
+        assert!(!board.contains("BSTF"));
+        assert!(board.ends_with(&format!("{}\n", legend())));
+    }
+}
@@ -0,0 +1,42 @@
+//! The random number generator behind the agents, searches and opening book. Each thread starts
+//! from operating system entropy, and [`seed`] makes what follows on the thread reproducible.
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+thread_local! {
+    static THREAD_RNG: Rc<RefCell<StdRng>> = Rc::new(RefCell::new(StdRng::from_entropy()));
+}
+
+/// A handle to the generator of the current thread, used like `rand::thread_rng()`.
+#[derive(Clone)]
+pub struct ThreadRng(Rc<RefCell<StdRng>>);
+
+impl RngCore for ThreadRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.borrow_mut().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.borrow_mut().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.borrow_mut().fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.borrow_mut().try_fill_bytes(dest)
+    }
+}
+
+pub fn thread_rng() -> ThreadRng {
+    ThreadRng(THREAD_RNG.with(Rc::clone))
+}
+
+/// Restarts the generator of the current thread from `seed`.
+pub fn seed(seed: u64) {
+    THREAD_RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
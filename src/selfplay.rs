@@ -0,0 +1,154 @@
+//! Training data from MCTS self-play, run by `quarto selfplay`.
+//!
+//! Files are little endian: the magic `QSPD`, the format version, [`INPUT_SIZE`] and
+//! [`POLICY_SIZE`] as u32 and the number of samples as u64. Each sample follows as its game and
+//! ply as u32, its [`encode`]d position and root visit distribution indexed by [`action_index`]
+//! as f32, and the outcome for the player to move as f32: 1 for a win, 0.5 for a draw and 0 for a
+//! loss.
+//!
+//! [`action_index`]: crate::nn::action_index
+
+use crate::montecarlo::mcts_search;
+use crate::nn::{action_index, encode, INPUT_SIZE, POLICY_SIZE};
+use crate::quarto::State;
+use crate::rng;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+const MAGIC: &[u8; 4] = b"QSPD";
+const VERSION: u32 = 1;
+
+/// One position of a self-play game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub game: u32,
+    pub ply: u32,
+    pub input: Vec<f32>,
+    pub policy: Vec<f32>,
+    pub outcome: f32,
+}
+
+/// Plays one game of MCTS against itself from `seed`, taking the most visited action of each
+/// search.
+pub fn play_game(game: u32, seed: u64, playout_number: usize) -> Vec<Sample> {
+    rng::seed(seed);
+    let mut state = State::new();
+    let mut samples = Vec::new();
+    let mut first_player = Vec::new();
+    while !state.is_done() {
+        let statistics = mcts_search(&state, playout_number);
+        let trials: i32 = statistics.iter().map(|s| s.trials).sum();
+        let mut policy = vec![0.0; POLICY_SIZE];
+        for s in &statistics {
+            policy[action_index(s.action)] = s.trials as f32 / trials as f32;
+        }
+        samples.push(Sample {
+            game,
+            ply: samples.len() as u32,
+            input: encode(&state),
+            policy,
+            outcome: 0.5,
+        });
+        first_player.push(state.is_first_player());
+        let best = statistics.iter().max_by_key(|s| s.trials).unwrap();
+        state.apply_action(best.action);
+    }
+    let score = state.get_first_player_score_for_win_rate() as f32;
+    for (sample, first) in samples.iter_mut().zip(first_player) {
+        sample.outcome = if first { score } else { 1.0 - score };
+    }
+    samples
+}
+
+/// Plays `game_number` games on `thread_number` threads, game `i` from seed `seed + i`, so the
+/// samples do not depend on the number of threads. They are returned in game order.
+pub fn generate(
+    game_number: usize,
+    playout_number: usize,
+    thread_number: usize,
+    seed: u64,
+) -> Vec<Sample> {
+    let next_game = AtomicUsize::new(0);
+    let games = Mutex::new(vec![Vec::new(); game_number]);
+    thread::scope(|scope| {
+        for _ in 0..thread_number.max(1) {
+            scope.spawn(|| loop {
+                let game = next_game.fetch_add(1, Ordering::Relaxed);
+                if game >= game_number {
+                    break;
+                }
+                let samples = play_game(game as u32, seed + game as u64, playout_number);
+                games.lock().unwrap()[game] = samples;
+            });
+        }
+    });
+    games.into_inner().unwrap().concat()
+}
+
+pub fn save(samples: &[Sample], path: &str) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(MAGIC)?;
+    for value in [VERSION, INPUT_SIZE as u32, POLICY_SIZE as u32] {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    writer.write_all(&(samples.len() as u64).to_le_bytes())?;
+    for sample in samples {
+        writer.write_all(&sample.game.to_le_bytes())?;
+        writer.write_all(&sample.ply.to_le_bytes())?;
+        for value in sample.input.iter().chain(&sample.policy) {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&sample.outcome.to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_bytes<const L: usize, R: Read>(reader: &mut R) -> io::Result<[u8; L]> {
+    let mut bytes = [0; L];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_f32s<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<f32>> {
+    (0..len)
+        .map(|_| Ok(f32::from_le_bytes(read_bytes(reader)?)))
+        .collect()
+}
+
+pub fn load(path: &str) -> io::Result<Vec<Sample>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if &read_bytes::<4, _>(&mut reader)? != MAGIC {
+        return Err(invalid("not a self-play file"));
+    }
+    if read_u32(&mut reader)? != VERSION {
+        return Err(invalid("unsupported self-play file version"));
+    }
+    if read_u32(&mut reader)? as usize != INPUT_SIZE
+        || read_u32(&mut reader)? as usize != POLICY_SIZE
+    {
+        return Err(invalid("the encoding does not match"));
+    }
+    let count = u64::from_le_bytes(read_bytes(&mut reader)?);
+    let mut samples = Vec::new();
+    for _ in 0..count {
+        samples.push(Sample {
+            game: read_u32(&mut reader)?,
+            ply: read_u32(&mut reader)?,
+            input: read_f32s(&mut reader, INPUT_SIZE)?,
+            policy: read_f32s(&mut reader, POLICY_SIZE)?,
+            outcome: f32::from_le_bytes(read_bytes(&mut reader)?),
+        });
+    }
+    Ok(samples)
+}
@@ -0,0 +1,137 @@
+//! An exact solver for small positions, used by the coach, the analysis and the tablebase.
+
+use crate::quarto::{Action, GameState, Objective, RuleSet, Symmetry, WinningStatus};
+use std::collections::HashMap;
+
+pub const LOSE: u8 = 0;
+pub const DRAW: u8 = 1;
+pub const WIN: u8 = 2;
+
+// Values are in half points for the player to move: `LOSE`, `DRAW` or `WIN`. Positions are
+// memoized by their canonical key under the board symmetries that keep the winning patterns of
+// their rules, and every memoized value is exact. Scored games are solved for their winner only.
+pub struct Solver<const N: usize = 4, const K: usize = 4> {
+    memo: HashMap<u128, u8>,
+    rules: RuleSet,
+    symmetries: Vec<Symmetry<N, K>>,
+}
+
+impl<const N: usize, const K: usize> Default for Solver<N, K> {
+    fn default() -> Self {
+        Solver {
+            memo: HashMap::new(),
+            rules: RuleSet::default(),
+            symmetries: Symmetry::generate_board(),
+        }
+    }
+}
+
+// The value of a finished game for the player who ended it.
+fn outcome<const N: usize, const K: usize>(state: &GameState<N, K>) -> u8 {
+    match state.get_winning_status() {
+        WinningStatus::WIN => WIN,
+        WinningStatus::LOSE => LOSE,
+        _ => DRAW,
+    }
+}
+
+// The value of claiming the open line, if there is one.
+fn claim<const N: usize, const K: usize>(state: &GameState<N, K>) -> Option<u8> {
+    if !state.can_claim() {
+        return None;
+    }
+    let mut claimed = *state;
+    claimed.apply_action((None, None));
+    Some(outcome(&claimed))
+}
+
+impl<const N: usize, const K: usize> Solver<N, K> {
+    pub fn new() -> Self {
+        Solver::default()
+    }
+
+    pub fn value(&mut self, state: &GameState<N, K>) -> u8 {
+        if state.rules() != self.rules {
+            self.rules = state.rules();
+            self.symmetries = Symmetry::generate_board()
+                .into_iter()
+                .filter(|symmetry| symmetry.keeps_patterns(self.rules))
+                .collect();
+            self.memo.clear();
+        }
+        // Missed wins are never better than calling them, as misère is not played with the call
+        // rule, so only a winning claim leaves the memo's positions.
+        if claim(state) == Some(WIN) {
+            return WIN;
+        }
+        let (key, _) = state.canonical(&self.symmetries);
+        if let Some(&value) = self.memo.get(&key) {
+            return value;
+        }
+        let value = self.search(state);
+        self.memo.insert(key, value);
+        value
+    }
+
+    fn search(&mut self, state: &GameState<N, K>) -> u8 {
+        let misere = state.rules().objective == Objective::Misere;
+        let placements = if state.is_first_turn() {
+            vec![None]
+        } else {
+            let placements = state.legal_placements();
+            if !misere
+                && placements
+                    .iter()
+                    .any(|&(h, w)| state.can_put_then_win(h, w))
+            {
+                return WIN;
+            }
+            placements.into_iter().map(Some).collect()
+        };
+
+        let mut best_value = LOSE;
+        for place in placements {
+            let mut next_state = *state;
+            if let Some((h, w)) = place {
+                next_state.put_piece(h, w);
+            }
+            if next_state.is_done() {
+                best_value = best_value.max(outcome(&next_state));
+                continue;
+            }
+            for piece in next_state.legal_pieces() {
+                let mut child_state = next_state;
+                child_state.select_piece(piece);
+                let value = WIN - self.value(&child_state);
+                if value == WIN {
+                    return WIN;
+                }
+                best_value = best_value.max(value);
+            }
+        }
+        best_value
+    }
+}
+
+// Exact value of every action for the player to move, for positions small enough to solve.
+pub fn solve_actions<const N: usize, const K: usize>(state: &GameState<N, K>) -> Vec<(Action, u8)> {
+    let mut solver = Solver::new();
+    let mut values = Vec::new();
+    if let Some(value) = claim(state) {
+        values.push(((None, None), value));
+    }
+    for place in state.legal_placements() {
+        let mut next_state = *state;
+        next_state.put_piece(place.0, place.1);
+        if next_state.is_done() {
+            values.push(((Some(place), None), outcome(&next_state)));
+            continue;
+        }
+        for piece in next_state.legal_pieces() {
+            let mut child_state = next_state;
+            child_state.select_piece(piece);
+            values.push(((Some(place), Some(piece)), WIN - solver.value(&child_state)));
+        }
+    }
+    values
+}
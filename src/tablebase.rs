@@ -0,0 +1,185 @@
+//! A cache of solved endgames for the classic rules, run by `quarto tablebase`.
+//!
+//! Every position with few enough empty squares is far too many for the 4x4 board, so the cache
+//! only holds the endgames reachable from sampled game positions: `quarto tablebase` samples 100
+//! games by default. Probes outside of them miss, and searches fall back to their playouts.
+
+use crate::agents::random_action;
+use crate::quarto::{RuleSet, State, Symmetry};
+use crate::solver::{Solver, WIN};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::sync::OnceLock;
+
+const MAGIC: &[u8; 4] = b"QTB1";
+
+const HEADER_SIZE: u64 = 13;
+
+const ENTRY_SIZE: u64 = 17;
+
+static TABLEBASE: OnceLock<Tablebase> = OnceLock::new();
+
+// Exact values of positions with at most `empty_limit` empty squares, keyed by
+// `State::canonical` over `Symmetry::all` and stored in half points for the player
+// to move (see `solver`).
+pub struct Tablebase {
+    empty_limit: usize,
+    values: HashMap<u128, u8>,
+}
+
+impl Tablebase {
+    /// Enumerates every position reachable from `roots` and solves those with at most
+    /// `empty_limit` empty squares.
+    pub fn from_roots(roots: &[State], empty_limit: usize) -> Self {
+        let mut solver = Solver::new();
+        let mut values = HashMap::new();
+        let mut visited = HashSet::new();
+        let mut stack = roots.to_vec();
+        while let Some(state) = stack.pop() {
+            if state.is_done() {
+                continue;
+            }
+            let (key, _) = state.canonical(Symmetry::all());
+            if !visited.insert(key) {
+                continue;
+            }
+            if !state.is_first_turn() && state.empty_count() <= empty_limit {
+                values.insert(key, solver.value(&state));
+            }
+
+            let placements = if state.is_first_turn() {
+                vec![None]
+            } else {
+                state.legal_placements().into_iter().map(Some).collect()
+            };
+            for place in placements {
+                let mut next_state = state;
+                if let Some((h, w)) = place {
+                    next_state.put_piece(h, w);
+                }
+                if next_state.is_done() {
+                    continue;
+                }
+                for piece in next_state.legal_pieces() {
+                    let mut child_state = next_state;
+                    child_state.select_piece(piece);
+                    stack.push(child_state);
+                }
+            }
+        }
+        Tablebase {
+            empty_limit,
+            values,
+        }
+    }
+
+    /// Solves the endgames of `root_number` random games from their first position with at most
+    /// `empty_limit` empty squares.
+    pub fn sample(empty_limit: usize, root_number: usize) -> Self {
+        Self::from_roots(&Self::sample_roots(empty_limit, root_number), empty_limit)
+    }
+
+    pub fn sample_roots(empty_limit: usize, root_number: usize) -> Vec<State> {
+        let mut roots = Vec::new();
+        while roots.len() < root_number {
+            let mut state = State::new();
+            while !state.is_done() && (state.is_first_turn() || state.empty_count() > empty_limit) {
+                state.apply_action(random_action(&state));
+            }
+            if !state.is_done() {
+                roots.push(state);
+            }
+        }
+        roots
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn probe(&self, state: &State) -> Option<f64> {
+        // The values are those of the classic rules.
+        if state.rules() != RuleSet::default()
+            || state.is_first_turn()
+            || state.empty_count() > self.empty_limit
+            || state.is_done()
+        {
+            return None;
+        }
+        let (key, _) = state.canonical(Symmetry::all());
+        self.values
+            .get(&key)
+            .map(|&value| value as f64 / WIN as f64)
+    }
+
+    // MAGIC, the empty square limit (u8), the entry count (u64 LE), then per entry the key
+    // (u128 LE) and the value (u8).
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[self.empty_limit as u8])?;
+        writer.write_all(&(self.values.len() as u64).to_le_bytes())?;
+        let mut entries: Vec<(&u128, &u8)> = self.values.iter().collect();
+        entries.sort();
+        for (key, value) in entries {
+            writer.write_all(&key.to_le_bytes())?;
+            writer.write_all(&[*value])?;
+        }
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+        let file = File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a quarto tablebase"));
+        }
+        let mut empty_limit = [0u8; 1];
+        reader.read_exact(&mut empty_limit)?;
+        let mut count = [0u8; 8];
+        reader.read_exact(&mut count)?;
+        // The count is checked against the file before anything is allocated for it.
+        let count = u64::from_le_bytes(count);
+        if count.checked_mul(ENTRY_SIZE) != Some(file_size - HEADER_SIZE) {
+            return Err(invalid("the entry count does not match the file size"));
+        }
+        let mut values = HashMap::with_capacity(count as usize);
+        let mut entry = [0u8; ENTRY_SIZE as usize];
+        for _ in 0..count {
+            reader.read_exact(&mut entry)?;
+            let key = u128::from_le_bytes(entry[..16].try_into().unwrap());
+            if entry[16] > WIN {
+                return Err(invalid("a value is out of range"));
+            }
+            values.insert(key, entry[16]);
+        }
+        Ok(Tablebase {
+            empty_limit: empty_limit[0] as usize,
+            values,
+        })
+    }
+}
+
+pub fn load(path: &str) -> io::Result<()> {
+    let tablebase = Tablebase::load(path)?;
+    if TABLEBASE.set(tablebase).is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "tablebase is already loaded",
+        ));
+    }
+    Ok(())
+}
+
+pub fn probe(state: &State) -> Option<f64> {
+    TABLEBASE.get()?.probe(state)
+}
@@ -0,0 +1,310 @@
+//! Training the network in [`crate::nn`] on [`selfplay`](crate::selfplay) data, run by
+//! `quarto train`. The policy head learns the visit distributions by cross-entropy over all of its
+//! logits and the value head the outcomes by binary cross-entropy, both with Adam. New weights are
+//! only promoted once they beat the previous network in a match.
+
+use crate::nn::{Dense, Network, NetworkPlayer};
+use crate::r#match::test_first_player_win_rate;
+use crate::rng::thread_rng;
+use crate::selfplay::Sample;
+use rand::seq::SliceRandom;
+use std::io;
+
+/// The score new weights need against the previous network to be promoted.
+pub const GATE_SCORE: f64 = 0.55;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrainConfig {
+    pub epochs: usize,
+    pub batch_size: usize,
+    pub learning_rate: f32,
+    /// The share of the games held out for validation.
+    pub validation_fraction: f64,
+}
+
+impl Default for TrainConfig {
+    fn default() -> Self {
+        TrainConfig {
+            epochs: 10,
+            batch_size: 64,
+            learning_rate: 1e-3,
+            validation_fraction: 0.1,
+        }
+    }
+}
+
+/// Mean losses per sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Loss {
+    pub policy: f32,
+    pub value: f32,
+}
+
+impl Loss {
+    pub fn total(&self) -> f32 {
+        self.policy + self.value
+    }
+}
+
+/// The losses after one epoch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Epoch {
+    pub training: Loss,
+    pub validation: Loss,
+}
+
+/// Splits samples into training and validation sets by game, the last games of the data going to
+/// validation, so that positions of one game are never on both sides.
+pub fn split(samples: &[Sample], validation_fraction: f64) -> (Vec<Sample>, Vec<Sample>) {
+    let games = samples.iter().map(|s| s.game + 1).max().unwrap_or(0);
+    let mut cutoff = (games as f64 * (1.0 - validation_fraction)).round() as u32;
+    if validation_fraction > 0.0 && games > 1 {
+        cutoff = cutoff.min(games - 1);
+    }
+    samples.iter().cloned().partition(|s| s.game < cutoff)
+}
+
+fn zeros(network: &Network) -> Network {
+    let zero = |layer: &Dense| Dense {
+        inputs: layer.inputs,
+        outputs: layer.outputs,
+        weights: vec![0.0; layer.weights.len()],
+        biases: vec![0.0; layer.biases.len()],
+    };
+    Network {
+        trunk: network.trunk.iter().map(zero).collect(),
+        policy: zero(&network.policy),
+        value: zero(&network.value),
+    }
+}
+
+fn parameters(network: &mut Network) -> Vec<&mut Vec<f32>> {
+    network
+        .trunk
+        .iter_mut()
+        .chain([&mut network.policy, &mut network.value])
+        .flat_map(|layer| [&mut layer.weights, &mut layer.biases])
+        .collect()
+}
+
+// Adds the gradient of `output = layer.forward(input)` given the gradient of the output, and
+// returns the gradient of the input.
+fn backward(layer: &Dense, gradient: &mut Dense, input: &[f32], output: &[f32]) -> Vec<f32> {
+    let mut input_gradient = vec![0.0; layer.inputs];
+    for (o, &d) in output.iter().enumerate() {
+        if d == 0.0 {
+            continue;
+        }
+        gradient.biases[o] += d;
+        let row = o * layer.inputs;
+        for (i, &x) in input.iter().enumerate() {
+            gradient.weights[row + i] += d * x;
+            input_gradient[i] += d * layer.weights[row + i];
+        }
+    }
+    input_gradient
+}
+
+/// The losses of the network over the samples.
+pub fn loss(network: &Network, samples: &[Sample]) -> Loss {
+    gradients(network, samples).1
+}
+
+/// The gradients of the mean losses over the batch, as a network of the same shape, and the
+/// losses themselves.
+pub fn gradients(network: &Network, batch: &[Sample]) -> (Network, Loss) {
+    let mut gradient = zeros(network);
+    let mut loss = Loss::default();
+    for sample in batch {
+        let mut activations = vec![sample.input.clone()];
+        for layer in &network.trunk {
+            let mut hidden = layer.forward(activations.last().unwrap());
+            for x in &mut hidden {
+                *x = x.max(0.0);
+            }
+            activations.push(hidden);
+        }
+        let hidden = activations.last().unwrap();
+
+        let logits = network.policy.forward(hidden);
+        let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let exps: Vec<f32> = logits.iter().map(|&l| (l - max).exp()).collect();
+        let total: f32 = exps.iter().sum();
+        let mut policy_delta = Vec::with_capacity(logits.len());
+        for ((&exp, &logit), &target) in exps.iter().zip(&logits).zip(&sample.policy) {
+            if target > 0.0 {
+                loss.policy -= target * (logit - max - total.ln());
+            }
+            policy_delta.push(exp / total - target);
+        }
+
+        let value = 1.0 / (1.0 + (-network.value.forward(hidden)[0]).exp());
+        let value = value.clamp(1e-7, 1.0 - 1e-7);
+        let outcome = sample.outcome;
+        loss.value -= outcome * value.ln() + (1.0 - outcome) * (1.0 - value).ln();
+
+        let mut delta = backward(&network.policy, &mut gradient.policy, hidden, &policy_delta);
+        let value_delta = backward(
+            &network.value,
+            &mut gradient.value,
+            hidden,
+            &[value - outcome],
+        );
+        for (d, v) in delta.iter_mut().zip(value_delta) {
+            *d += v;
+        }
+        for (i, layer) in network.trunk.iter().enumerate().rev() {
+            for (d, &a) in delta.iter_mut().zip(&activations[i + 1]) {
+                if a <= 0.0 {
+                    *d = 0.0;
+                }
+            }
+            delta = backward(layer, &mut gradient.trunk[i], &activations[i], &delta);
+        }
+    }
+    let scale = 1.0 / batch.len().max(1) as f32;
+    for values in parameters(&mut gradient) {
+        for value in values {
+            *value *= scale;
+        }
+    }
+    loss.policy *= scale;
+    loss.value *= scale;
+    (gradient, loss)
+}
+
+/// Adam over the parameters of a network.
+pub struct Trainer {
+    pub network: Network,
+    learning_rate: f32,
+    step: i32,
+    first_moment: Network,
+    second_moment: Network,
+}
+
+impl Trainer {
+    const BETA1: f32 = 0.9;
+    const BETA2: f32 = 0.999;
+    const EPSILON: f32 = 1e-8;
+
+    pub fn new(network: Network, learning_rate: f32) -> Self {
+        Trainer {
+            first_moment: zeros(&network),
+            second_moment: zeros(&network),
+            network,
+            learning_rate,
+            step: 0,
+        }
+    }
+
+    /// One update on the batch, returning its losses before the update.
+    pub fn step(&mut self, batch: &[Sample]) -> Loss {
+        let (mut gradient, loss) = gradients(&self.network, batch);
+        self.step += 1;
+        let rate = self.learning_rate * (1.0 - Self::BETA2.powi(self.step)).sqrt()
+            / (1.0 - Self::BETA1.powi(self.step));
+        let parameters = parameters(&mut self.network)
+            .into_iter()
+            .zip(parameters(&mut gradient))
+            .zip(parameters(&mut self.first_moment))
+            .zip(parameters(&mut self.second_moment));
+        for (((values, gradients), first), second) in parameters {
+            for i in 0..values.len() {
+                let g = gradients[i];
+                first[i] = Self::BETA1 * first[i] + (1.0 - Self::BETA1) * g;
+                second[i] = Self::BETA2 * second[i] + (1.0 - Self::BETA2) * g * g;
+                values[i] -= rate * first[i] / (second[i].sqrt() + Self::EPSILON);
+            }
+        }
+        loss
+    }
+
+    /// One pass over the samples in a random order, returning the mean losses of its batches.
+    pub fn epoch(&mut self, samples: &[Sample], batch_size: usize) -> Loss {
+        let mut order: Vec<&Sample> = samples.iter().collect();
+        order.shuffle(&mut thread_rng());
+        let mut total = Loss::default();
+        let mut batches = 0;
+        for chunk in order.chunks(batch_size.max(1)) {
+            let batch: Vec<Sample> = chunk.iter().map(|&s| s.clone()).collect();
+            let loss = self.step(&batch);
+            total.policy += loss.policy;
+            total.value += loss.value;
+            batches += 1;
+        }
+        if batches > 0 {
+            total.policy /= batches as f32;
+            total.value /= batches as f32;
+        }
+        total
+    }
+}
+
+/// Trains the network for `config.epochs` epochs, saving the weights after each epoch to
+/// `<checkpoint>.<epoch>` when a checkpoint path is given. Returns the weights with the lowest
+/// validation loss, or the last ones without validation data, and the losses of every epoch, or
+/// the error of the first checkpoint that could not be written.
+pub fn train(
+    network: Network,
+    samples: &[Sample],
+    config: &TrainConfig,
+    checkpoint: Option<&str>,
+) -> io::Result<(Network, Vec<Epoch>)> {
+    let (training, validation) = split(samples, config.validation_fraction);
+    let mut trainer = Trainer::new(network, config.learning_rate);
+    let mut best = (f32::INFINITY, trainer.network.clone());
+    let mut epochs = Vec::new();
+    for epoch in 1..=config.epochs {
+        let training = trainer.epoch(&training, config.batch_size);
+        let validation = loss(&trainer.network, &validation);
+        if let Some(path) = checkpoint {
+            trainer.network.save(&format!("{}.{}", path, epoch))?;
+        }
+        if validation.total() <= best.0 {
+            best = (validation.total(), trainer.network.clone());
+        }
+        epochs.push(Epoch {
+            training,
+            validation,
+        });
+    }
+    Ok((best.1, epochs))
+}
+
+/// The score of `candidate` against `previous` over `game_number` games as each player, from 0
+/// for losing every game to 1 for winning every game.
+pub fn gate(
+    candidate: &Network,
+    previous: &Network,
+    game_number: i32,
+    playout_number: usize,
+) -> f64 {
+    let mut candidate_player = NetworkPlayer {
+        network: candidate.clone(),
+        playout_number,
+    };
+    let mut previous_player = NetworkPlayer {
+        network: previous.clone(),
+        playout_number,
+    };
+    let outcomes = test_first_player_win_rate(
+        game_number,
+        (
+            ("candidate", &mut candidate_player),
+            ("previous", &mut previous_player),
+        ),
+    );
+    let score: f64 = outcomes
+        .iter()
+        .enumerate()
+        .map(|(i, outcome)| {
+            let score = outcome.first_player_score();
+            if i % 2 == 0 {
+                score
+            } else {
+                1.0 - score
+            }
+        })
+        .sum();
+    score / outcomes.len().max(1) as f64
+}
@@ -0,0 +1,510 @@
+//! A full screen terminal game against an engine, run by `quarto tui`.
+//!
+//! The human moves a cursor over the board and the palette of unused pieces, and can take back
+//! moves and ask for hints from an MCTS search.
+
+use crate::montecarlo::{mcts_search, ActionStatistics};
+use crate::play::ActionFn;
+use crate::quarto::{Action, GameState};
+use crate::render::{legend, piece_glyph, styled_cell};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::io::{self, Stdout, Write};
+
+const HINT_PLAYOUTS: usize = 10000;
+
+const LOG_LINES: usize = 8;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Phase {
+    Place,
+    Select,
+    Engine,
+    Done,
+}
+
+struct Snapshot<const N: usize, const K: usize> {
+    state: GameState<N, K>,
+    log_len: usize,
+}
+
+struct Game<const N: usize, const K: usize> {
+    state: GameState<N, K>,
+    human_player: usize,
+    engine: fn(&GameState<N, K>) -> Action,
+    // The search behind hints, given the number of playouts, which returns the root statistics.
+    hint_engine: fn(&GameState<N, K>, usize) -> Vec<ActionStatistics>,
+    phase: Phase,
+    cursor: (usize, usize),
+    palette_cursor: usize,
+    pending_place: Option<(usize, usize)>,
+    history: Vec<Snapshot<N, K>>,
+    log: Vec<String>,
+    message: String,
+}
+
+// Restores the terminal even when the game loop returns early with an error.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new(stdout: &mut Stdout) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(stdout, EnterAlternateScreen, Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn player_name(player: usize) -> &'static str {
+    if player == 0 {
+        "1p"
+    } else {
+        "2p"
+    }
+}
+
+fn describe_action((place, piece): Action) -> String {
+    let mut description = Vec::new();
+    if let Some((h, w)) = place {
+        description.push(format!("put ({}, {})", h, w));
+    }
+    if let Some(piece) = piece {
+        description.push(format!("select {}", piece));
+    }
+    description.join(", ")
+}
+
+impl<const N: usize, const K: usize> Game<N, K> {
+    fn new(
+        engine: fn(&GameState<N, K>) -> Action,
+        hint_engine: fn(&GameState<N, K>, usize) -> Vec<ActionStatistics>,
+        human_player: usize,
+    ) -> Self {
+        Game {
+            state: GameState::new(),
+            human_player,
+            engine,
+            hint_engine,
+            phase: Phase::Engine,
+            cursor: (0, 0),
+            palette_cursor: 0,
+            pending_place: None,
+            history: Vec::new(),
+            log: Vec::new(),
+            message: String::new(),
+        }
+    }
+
+    // `State` flips the active player when a piece is handed over, so the active player is the
+    // one who places the piece in hand (or picks the first piece).
+    fn mover(&self) -> usize {
+        if self.state.is_first_player() {
+            0
+        } else {
+            1
+        }
+    }
+
+    fn start_turn(&mut self) {
+        if self.state.is_done() {
+            self.phase = Phase::Done;
+        } else if self.mover() != self.human_player {
+            self.phase = Phase::Engine;
+        } else if self.state.is_first_turn() {
+            self.phase = Phase::Select;
+        } else {
+            self.phase = Phase::Place;
+        }
+        self.palette_cursor = 0;
+    }
+
+    fn commit(&mut self, action: Action) {
+        let mover = self.mover();
+        self.state.apply_action(action);
+        self.log.push(format!(
+            "{}: {}",
+            player_name(mover),
+            describe_action(action)
+        ));
+        self.start_turn();
+    }
+
+    fn play_engine(&mut self) {
+        let action = (self.engine)(&self.state);
+        self.commit(action);
+    }
+
+    fn place(&mut self) {
+        let (h, w) = self.cursor;
+        if !self.state.legal_placements().contains(&(h, w)) {
+            self.message = String::from("that square is taken");
+            return;
+        }
+        self.history.push(Snapshot {
+            state: self.state,
+            log_len: self.log.len(),
+        });
+        if self.state.can_put_then_win(h, w) || self.state.is_last_turn() {
+            self.commit((Some((h, w)), None));
+        } else {
+            self.pending_place = Some((h, w));
+            self.phase = Phase::Select;
+        }
+    }
+
+    fn select(&mut self) {
+        let pieces = self.state.legal_pieces();
+        let piece = pieces[self.palette_cursor.min(pieces.len() - 1)];
+        if self.pending_place.is_none() {
+            self.history.push(Snapshot {
+                state: self.state,
+                log_len: self.log.len(),
+            });
+        }
+        let place = self.pending_place.take();
+        self.commit((place, Some(piece)));
+    }
+
+    fn undo(&mut self) {
+        if self.pending_place.take().is_some() {
+            self.history.pop();
+            self.phase = Phase::Place;
+            return;
+        }
+        match self.history.pop() {
+            Some(snapshot) => {
+                self.state = snapshot.state;
+                self.log.truncate(snapshot.log_len);
+                self.start_turn();
+                self.message = String::from("took back the last move");
+            }
+            None => self.message = String::from("nothing to undo"),
+        }
+    }
+
+    fn hint(&mut self) {
+        if self.phase != Phase::Place && self.phase != Phase::Select {
+            return;
+        }
+        let statistics = (self.hint_engine)(&self.state, HINT_PLAYOUTS);
+        // Once a placement is pending, only the actions that make it are left to choose from.
+        let best = statistics
+            .iter()
+            .filter(|statistics| {
+                self.pending_place.is_none() || statistics.action.0 == self.pending_place
+            })
+            .max_by_key(|statistics| statistics.trials);
+        let Some(&ActionStatistics {
+            action: (place, piece),
+            ..
+        }) = best
+        else {
+            return;
+        };
+        if let Some(place) = place {
+            self.cursor = place;
+        }
+        if let Some(piece) = piece {
+            if let Some(idx) = self.state.legal_pieces().iter().position(|&p| p == piece) {
+                self.palette_cursor = idx;
+            }
+        }
+        self.message = format!("hint: {}", describe_action((place, piece)));
+    }
+
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        self.message.clear();
+        let piece_number = self.state.legal_pieces().len().max(1);
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('u') => self.undo(),
+            KeyCode::Char('h') => self.hint(),
+            KeyCode::Up if self.phase == Phase::Place => {
+                self.cursor.0 = (self.cursor.0 + N - 1) % N;
+            }
+            KeyCode::Down if self.phase == Phase::Place => {
+                self.cursor.0 = (self.cursor.0 + 1) % N;
+            }
+            KeyCode::Left if self.phase == Phase::Place => {
+                self.cursor.1 = (self.cursor.1 + N - 1) % N;
+            }
+            KeyCode::Right if self.phase == Phase::Place => {
+                self.cursor.1 = (self.cursor.1 + 1) % N;
+            }
+            KeyCode::Left | KeyCode::Up if self.phase == Phase::Select => {
+                self.palette_cursor = (self.palette_cursor + piece_number - 1) % piece_number;
+            }
+            KeyCode::Right | KeyCode::Down if self.phase == Phase::Select => {
+                self.palette_cursor = (self.palette_cursor + 1) % piece_number;
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => match self.phase {
+                Phase::Place => self.place(),
+                Phase::Select => self.select(),
+                _ => {}
+            },
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&self, stdout: &mut Stdout) -> io::Result<()> {
+        queue!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+        let mut row = 0;
+        let line = |stdout: &mut Stdout, row: &mut u16| -> io::Result<()> {
+            *row += 1;
+            queue!(stdout, MoveTo(0, *row))
+        };
+
+        queue!(
+            stdout,
+            Print(format!(
+                "Quarto  turn {}  you are {}",
+                self.state.turn(),
+                player_name(self.human_player)
+            ))
+        )?;
+        line(stdout, &mut row)?;
+
+        let winning_cells: Vec<(usize, usize)> =
+            self.state.winning_lines().into_iter().flatten().collect();
+        let columns: Vec<String> = (0..N).map(|w| w.to_string()).collect();
+        let separator = format!("  +{}", "------+".repeat(N));
+        line(stdout, &mut row)?;
+        queue!(stdout, Print(format!("      {}", columns.join("      "))))?;
+        for h in 0..N {
+            line(stdout, &mut row)?;
+            queue!(stdout, Print(&separator))?;
+            line(stdout, &mut row)?;
+            queue!(stdout, Print(format!("{} |", h)))?;
+            for w in 0..N {
+                let (piece, background) = match self.state.get_piece(h, w) {
+                    Some(piece) if winning_cells.contains(&(h, w)) => {
+                        (Some(piece), Some(Color::DarkGreen))
+                    }
+                    Some(piece) => (Some(piece), None),
+                    None if self.pending_place == Some((h, w)) => {
+                        (self.state.selected_piece(), Some(Color::DarkGrey))
+                    }
+                    None => (None, None),
+                };
+                let reverse = self.phase == Phase::Place && self.cursor == (h, w);
+                queue!(
+                    stdout,
+                    Print(styled_cell(piece, background, reverse)),
+                    Print("|")
+                )?;
+            }
+        }
+        line(stdout, &mut row)?;
+        queue!(stdout, Print(&separator))?;
+        line(stdout, &mut row)?;
+
+        line(stdout, &mut row)?;
+        queue!(stdout, Print("in hand: "))?;
+        if let Some(piece) = self.state.selected_piece() {
+            queue!(
+                stdout,
+                Print(piece_glyph(piece)),
+                Print(" "),
+                SetAttribute(Attribute::Bold),
+                SetBackgroundColor(Color::DarkYellow),
+                Print(format!(" {} ", piece)),
+                SetAttribute(Attribute::Reset),
+                ResetColor
+            )?;
+        }
+        line(stdout, &mut row)?;
+        queue!(stdout, Print("pieces:  "))?;
+        for (i, piece) in self.state.legal_pieces().iter().enumerate() {
+            if i == GameState::<N, K>::PIECE_NUMBER / 2 {
+                line(stdout, &mut row)?;
+                queue!(stdout, Print("         "))?;
+            }
+            queue!(stdout, Print(" "), Print(piece_glyph(*piece)))?;
+            if self.phase == Phase::Select && i == self.palette_cursor {
+                queue!(stdout, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(
+                stdout,
+                Print(format!(" {} ", piece)),
+                SetAttribute(Attribute::Reset)
+            )?;
+        }
+        line(stdout, &mut row)?;
+
+        line(stdout, &mut row)?;
+        queue!(stdout, Print("log:"))?;
+        for entry in self
+            .log
+            .iter()
+            .skip(self.log.len().saturating_sub(LOG_LINES))
+        {
+            line(stdout, &mut row)?;
+            queue!(stdout, Print(format!("  {}", entry)))?;
+        }
+        line(stdout, &mut row)?;
+
+        line(stdout, &mut row)?;
+        let status = match self.phase {
+            Phase::Place => String::from("place the piece in hand"),
+            Phase::Select => String::from("choose a piece for your opponent"),
+            Phase::Engine => String::from("engine is thinking..."),
+            Phase::Done => {
+                if self.state.can_win() {
+                    format!("{} wins", player_name(self.mover()))
+                } else {
+                    String::from("draw")
+                }
+            }
+        };
+        queue!(stdout, Print(status))?;
+        line(stdout, &mut row)?;
+        queue!(stdout, Print(&self.message))?;
+        line(stdout, &mut row)?;
+        queue!(
+            stdout,
+            Print("arrows: move  enter: confirm  u: undo  h: hint  q: quit")
+        )?;
+        line(stdout, &mut row)?;
+        queue!(stdout, Print(legend()))?;
+        stdout.flush()
+    }
+}
+
+pub fn run(engine: ActionFn, human_player: usize) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    let _guard = TerminalGuard::new(&mut stdout)?;
+    let mut game = Game::new(engine, mcts_search, human_player);
+    game.start_turn();
+    loop {
+        game.draw(&mut stdout)?;
+        if game.phase == Phase::Engine {
+            game.play_engine();
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !game.handle_key(key.code) {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Small = GameState<3, 3>;
+
+    fn first_action(state: &Small) -> Action {
+        state.legal_actions()[0]
+    }
+
+    // Statistics in which the earlier legal actions were visited more.
+    fn first_hint(state: &Small, _: usize) -> Vec<ActionStatistics> {
+        let actions = state.legal_actions();
+        let action_number = actions.len() as i32;
+        actions
+            .into_iter()
+            .zip(0..)
+            .map(|(action, i)| ActionStatistics {
+                action,
+                trials: action_number - i,
+                value: 0.5,
+            })
+            .collect()
+    }
+
+    fn press(game: &mut Game<3, 3>, codes: &[KeyCode]) {
+        for &code in codes {
+            assert!(game.handle_key(code));
+        }
+    }
+
+    #[test]
+    fn keys_pick_pieces_and_cells() {
+        let mut game = Game::new(first_action, first_hint, 0);
+        game.start_turn();
+        assert!(game.phase == Phase::Select);
+        let piece = game.state.legal_pieces()[2];
+        press(&mut game, &[KeyCode::Right, KeyCode::Down, KeyCode::Enter]);
+        assert_eq!(game.state.selected_piece(), Some(piece));
+        assert!(game.phase == Phase::Engine);
+
+        game.play_engine();
+        assert!(game.phase == Phase::Place);
+        let hand = game.state.selected_piece().unwrap();
+        // The cursor wraps around the 3x3 board.
+        press(&mut game, &[KeyCode::Up, KeyCode::Left, KeyCode::Left]);
+        assert_eq!(game.cursor, (2, 1));
+        press(&mut game, &[KeyCode::Enter]);
+        assert!(game.phase == Phase::Select);
+        assert_eq!(game.pending_place, Some((2, 1)));
+        let piece = game.state.legal_pieces()[game.state.legal_pieces().len() - 1];
+        press(&mut game, &[KeyCode::Left, KeyCode::Char(' ')]);
+        assert_eq!(game.state.get_piece(2, 1), Some(hand));
+        assert_eq!(game.state.selected_piece(), Some(piece));
+        assert_eq!(game.log.len(), 3);
+    }
+
+    #[test]
+    fn taken_cells_and_undo() {
+        let mut game = Game::new(first_action, first_hint, 0);
+        game.start_turn();
+        press(&mut game, &[KeyCode::Enter]);
+        game.play_engine();
+        // The engine placed its piece on the first cell.
+        assert!(game.state.get_piece(0, 0).is_some());
+        let state = game.state;
+        press(&mut game, &[KeyCode::Enter]);
+        assert_eq!(game.message, "that square is taken");
+        assert_eq!(game.state, state);
+
+        game.cursor = (2, 2);
+        press(&mut game, &[KeyCode::Enter]);
+        assert!(game.phase == Phase::Select);
+        // Undo first drops the pending placement, then takes back the last move.
+        press(&mut game, &[KeyCode::Char('u')]);
+        assert!(game.phase == Phase::Place);
+        assert_eq!(game.pending_place, None);
+        press(&mut game, &[KeyCode::Char('u')]);
+        assert!(game.state.is_first_turn());
+        assert!(game.phase == Phase::Select);
+        assert!(game.log.is_empty());
+
+        press(&mut game, &[KeyCode::Char('h')]);
+        assert!(game.message.starts_with("hint: select"));
+        assert!(!game.handle_key(KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn hints_keep_the_pending_placement() {
+        let mut game = Game::new(first_action, first_hint, 0);
+        game.start_turn();
+        press(&mut game, &[KeyCode::Enter]);
+        game.play_engine();
+        press(&mut game, &[KeyCode::Char('h')]);
+        assert!(game.message.starts_with("hint: put (0, 1), select"));
+
+        game.cursor = (2, 2);
+        press(&mut game, &[KeyCode::Enter]);
+        assert_eq!(game.pending_place, Some((2, 2)));
+        press(&mut game, &[KeyCode::Char('h')]);
+        assert!(
+            game.message.starts_with("hint: put (2, 2), select"),
+            "{}",
+            game.message
+        );
+        assert_eq!(game.cursor, (2, 2));
+    }
+}
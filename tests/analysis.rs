@@ -0,0 +1,135 @@
+use quarto::agents::random_action;
+use quarto::analysis::{Analysis, Judgement};
+use quarto::play::GameRecord;
+use quarto::quarto::{RuleSet, State};
+use quarto::rng;
+use quarto::solver::{solve_actions, LOSE, WIN};
+
+fn path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!(
+            "quarto-analysis-{}-{}.txt",
+            std::process::id(),
+            name
+        ))
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+fn random_game(rules: RuleSet) -> GameRecord {
+    let mut record = GameRecord {
+        rules,
+        ..GameRecord::new()
+    };
+    let mut state = State::new().with_rules(rules);
+    while !state.is_done() {
+        let action = random_action(&state);
+        state.apply_action(action);
+        record.actions.push(action);
+    }
+    record
+}
+
+// A game in which a player with a won position plays a losing action once the position is small
+// enough to be solved, the other actions being random. Returns the game and the blundering ply.
+fn game_with_blunder() -> (GameRecord, usize) {
+    loop {
+        let mut record = GameRecord::new();
+        let mut state = State::new();
+        let mut blunder = None;
+        while !state.is_done() {
+            let mut action = random_action(&state);
+            if blunder.is_none() && state.empty_count() <= 6 {
+                let values = solve_actions(&state);
+                let best = values.iter().map(|&(_, value)| value).max().unwrap();
+                if best == WIN {
+                    if let Some(&(losing, _)) = values.iter().find(|&&(_, value)| value == LOSE) {
+                        action = losing;
+                        blunder = Some(record.actions.len());
+                    }
+                }
+            }
+            state.apply_action(action);
+            record.actions.push(action);
+        }
+        if let Some(ply) = blunder {
+            return (record, ply);
+        }
+    }
+}
+
+#[test]
+fn analyses_reload_with_their_rules() {
+    rng::seed(1);
+    for rules in ["misere", "squares,call", "toroidal,scored"] {
+        let mut record = random_game(rules.parse().unwrap());
+        record.actions.truncate(10);
+        record.resigned = Some(1);
+        let analysis = Analysis::analyze(&record, 50);
+        assert_eq!(analysis.rules, record.rules);
+        let text = analysis.to_string();
+        assert!(text.contains(&format!("\nrules {}\n", rules)), "{}", text);
+        let path = path(rules);
+        std::fs::write(&path, text).unwrap();
+        let loaded = GameRecord::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), record);
+    }
+    let record = random_game(RuleSet::default());
+    assert!(!Analysis::analyze(&record, 50).to_string().contains("rules"));
+}
+
+#[test]
+fn losing_a_won_position_is_a_blunder() {
+    rng::seed(2);
+    let (record, blunder) = game_with_blunder();
+    let analysis = Analysis::analyze(&record, 50);
+    assert_eq!(analysis.plies.len(), record.actions.len());
+    let ply = &analysis.plies[blunder];
+    assert_eq!(ply.player, blunder % 2);
+    assert_eq!(ply.action, record.actions[blunder]);
+    assert!(ply.exact);
+    assert_eq!((ply.before, ply.after), (1.0, 0.0));
+    assert_eq!(ply.loss(), 1.0);
+    assert_eq!(ply.judgement, Judgement::Blunder);
+    assert_ne!(ply.best_action, ply.action);
+
+    let mut state = State::new();
+    for &action in &record.actions[..blunder] {
+        state.apply_action(action);
+    }
+    let best = solve_actions(&state)
+        .into_iter()
+        .find(|&(action, _)| action == ply.best_action)
+        .unwrap();
+    assert_eq!(best.1, WIN);
+
+    let line = analysis
+        .to_string()
+        .lines()
+        .nth(blunder + 1)
+        .unwrap()
+        .to_string();
+    assert!(line.contains(" blunder, best "), "{}", line);
+}
+
+#[test]
+fn best_actions_are_good() {
+    rng::seed(3);
+    let (record, _) = game_with_blunder();
+    let analysis = Analysis::analyze(&record, 50);
+    for ply in &analysis.plies {
+        assert!((0.0..=1.0).contains(&ply.before));
+        assert!((0.0..=1.0).contains(&ply.after));
+        if ply.action == ply.best_action {
+            assert_eq!(ply.loss(), 0.0);
+            assert_eq!(ply.judgement, Judgement::Good);
+        }
+        if ply.exact && ply.loss() == 0.0 {
+            assert_eq!(ply.judgement, Judgement::Good);
+        }
+    }
+    // The last action ends the game, so its evaluation is exact.
+    assert!(analysis.plies.last().unwrap().exact);
+}
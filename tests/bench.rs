@@ -0,0 +1,45 @@
+use quarto::agents::{mcts_action, random_action};
+use quarto::bench;
+use quarto::quarto::{Action, State};
+use quarto::rng;
+use std::time::Duration;
+
+fn seeded_game(seed: u64) -> Vec<Action> {
+    rng::seed(seed);
+    let mut state = State::new();
+    let mut actions = Vec::new();
+    while !state.is_done() {
+        let action = if actions.len() % 2 == 0 {
+            mcts_action(&state, 50)
+        } else {
+            random_action(&state)
+        };
+        state.apply_action(action);
+        actions.push(action);
+    }
+    actions
+}
+
+#[test]
+fn seeds_reproduce_games() {
+    assert_eq!(seeded_game(7), seeded_game(7));
+    assert_ne!(seeded_game(7), seeded_game(8));
+}
+
+#[test]
+fn baselines_round_trip() {
+    let measurements = bench::run(Some("legal_placements"), Duration::ZERO);
+    assert_eq!(measurements.len(), 1);
+    assert_eq!(measurements[0].name, "legal_placements");
+    assert_eq!(measurements[0].iterations, 1);
+
+    let path = std::env::temp_dir().join(format!("quarto-bench-{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    bench::save(&measurements, path).unwrap();
+    assert_eq!(bench::load(path).unwrap(), measurements);
+    std::fs::remove_file(path).unwrap();
+
+    let report = bench::report(&measurements, Some(&measurements));
+    assert!(report.contains("+0.0%"), "{}", report);
+    assert!(bench::run(Some("nothing"), Duration::ZERO).is_empty());
+}
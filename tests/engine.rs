@@ -0,0 +1,102 @@
+use quarto::engine::{run, Agent};
+use quarto::quarto::{parse_action, Action, Piece, State};
+use std::time::{Duration, Instant};
+
+fn run_script(agent: Agent, script: &str) -> Vec<String> {
+    let output = run(agent, script.as_bytes(), Vec::new()).unwrap();
+    String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+fn bestmove(lines: &[String]) -> Action {
+    let line = lines
+        .iter()
+        .rev()
+        .find(|line| line.starts_with("bestmove"))
+        .unwrap();
+    parse_action(line.strip_prefix("bestmove ").unwrap()).unwrap()
+}
+
+#[test]
+fn go_plays_a_legal_move_after_moves() {
+    let script = "position startpos moves - BSSF a1 WCTH\ngo playouts 10000\n";
+    let lines = run_script(Agent::Mcts, script);
+    assert!(lines
+        .iter()
+        .any(|line| line.starts_with("info playouts 10000")));
+    let mut state = State::new();
+    state.apply_action(parse_action("- BSSF").unwrap());
+    state.apply_action(parse_action("a1 WCTH").unwrap());
+    assert!(state.is_legal_action(bestmove(&lines)));
+}
+
+#[test]
+fn other_agents_answer() {
+    let position = "position BSSF.../..../..../.... WCTH\n";
+    for agent in [Agent::Rave, Agent::Primitive, Agent::Random] {
+        let lines = run_script(agent, &format!("{}go playouts 500\n", position));
+        let state = State::from_notation("BSSF.../..../..../.... WCTH").unwrap();
+        assert!(state.is_legal_action(bestmove(&lines)));
+    }
+}
+
+#[test]
+fn positions_carry_the_rules() {
+    let mut state = State::from_notation("BSSFBSSHBSTF./..../..../.... BSTH call").unwrap();
+    state.apply_action((Some((0, 3)), Some(Piece::from_index(15))));
+    let script = format!("position {}\ngo playouts 100\n", state.notation());
+    assert_eq!(bestmove(&run_script(Agent::Mcts, &script)), (None, None));
+    let script = format!(
+        "position {} moves - -\ngo\n",
+        state.notation().replace(" call open", " misere")
+    );
+    let lines = run_script(Agent::Random, &script);
+    assert_eq!(lines[0], "info string illegal move: - -");
+}
+
+#[test]
+fn stop_ends_a_long_search() {
+    let start = Instant::now();
+    let lines = run_script(
+        Agent::Mcts,
+        "position startpos moves - BSSF\ngo movetime 60000\nstop\nisready\n",
+    );
+    assert!(start.elapsed() < Duration::from_secs(30));
+    assert_eq!(lines.last().unwrap(), "readyok");
+    assert!(lines.iter().any(|line| line.starts_with("bestmove ")));
+}
+
+#[test]
+fn isready_is_answered_during_a_search() {
+    let start = Instant::now();
+    let lines = run_script(
+        Agent::Mcts,
+        "position startpos moves - BSSF\ngo movetime 60000\nisready\nstop\n",
+    );
+    assert!(start.elapsed() < Duration::from_secs(30));
+    let ready = lines.iter().position(|line| line == "readyok").unwrap();
+    let best = lines
+        .iter()
+        .position(|line| line.starts_with("bestmove "))
+        .unwrap();
+    assert!(ready < best, "{:?}", lines);
+}
+
+#[test]
+fn errors_are_reported() {
+    let lines = run_script(
+        Agent::Random,
+        "position startpos moves a1 BSSF\nfly\ngo playouts x\n",
+    );
+    assert_eq!(
+        lines,
+        [
+            "info string illegal move: a1 BSSF",
+            "info string unknown command: fly",
+            "info string invalid playouts: x",
+        ]
+    );
+}
@@ -0,0 +1,74 @@
+use quarto::agents::{random_action, ActionFn};
+use quarto::external::ExternalPlayer;
+use quarto::play::{Decision, Player};
+use quarto::quarto::State;
+use quarto::r#match::{play_match_game, GameOutcome};
+use std::path::PathBuf;
+use std::time::Duration;
+
+// The engine and its script, which the test removes once it is done with the engine.
+fn script(name: &str, body: &str) -> (ExternalPlayer, PathBuf) {
+    let path = std::env::temp_dir().join(format!(
+        "quarto-external-{}-{}.sh",
+        std::process::id(),
+        name
+    ));
+    std::fs::write(&path, body).unwrap();
+    let player = ExternalPlayer::spawn(
+        &format!("sh {}", path.display()),
+        Duration::from_millis(200),
+    )
+    .unwrap();
+    (player, path)
+}
+
+fn forfeit_reason(player: &mut ExternalPlayer) -> String {
+    let mut random: ActionFn = random_action;
+    match play_match_game([player as &mut dyn Player, &mut random]).1 {
+        GameOutcome::Forfeit { player: 0, reason } => reason,
+        _ => panic!("expected the external player to forfeit"),
+    }
+}
+
+#[test]
+fn answers_are_parsed() {
+    let (mut player, path) = script(
+        "answer",
+        "while read line; do case $line in go*) echo 'bestmove - BSSF';; esac; done\n",
+    );
+    match player.decide(&State::new()) {
+        Decision::Act(action) => assert!(action == (None, Some("BSSF".parse().unwrap()))),
+        _ => panic!("expected an action"),
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn illegal_actions_forfeit() {
+    let (mut player, path) = script(
+        "illegal",
+        "while read line; do case $line in go*) echo 'bestmove a1 BSSF';; esac; done\n",
+    );
+    assert_eq!(forfeit_reason(&mut player), "illegal action a1 BSSF");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn timeouts_forfeit() {
+    let (mut player, path) = script("silent", "while read line; do :; done\n");
+    assert_eq!(forfeit_reason(&mut player), "no answer within 200 ms");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn crashes_forfeit_and_restart() {
+    let (mut player, path) = script("crash", "read line; read line; exit 3\n");
+    assert!(forfeit_reason(&mut player).starts_with("the engine"));
+    // A restarted engine reads the position and crashes at the next `go` again.
+    player.new_game();
+    match player.decide(&State::new()) {
+        Decision::Forfeit(reason) => assert_ne!(reason, "the engine is not running"),
+        _ => panic!("expected the restarted engine to crash again"),
+    }
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,136 @@
+use quarto::http::{serve, serve_with_timeout};
+use quarto::quarto::State;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn start() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || serve(&listener));
+    address
+}
+
+fn request(address: &str, method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response[9..12].parse().unwrap();
+    let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+    (status, body)
+}
+
+#[test]
+fn play_a_game() {
+    let address = start();
+    let (status, body) = request(&address, "POST", "/games", "");
+    assert_eq!(status, 201);
+    assert!(body.starts_with("{\"id\":1,"));
+    assert!(body.contains("\"status\":\"playing\""));
+    assert!(body.contains("{\"place\":null,\"piece\":\"BSSF\"}"));
+
+    let action = "{\"place\": null, \"piece\": \"BSSF\"}";
+    let (status, body) = request(&address, "POST", "/games/1/actions", action);
+    assert_eq!(status, 200);
+    assert!(body.contains("\"actions\":[\"- BSSF\"]"));
+    assert!(body.contains("\"piece_in_hand\":\"BSSF\""));
+    assert!(body.contains("\"to_move\":\"2p\""));
+
+    let (status, body) = request(&address, "POST", "/games/1/actions", action);
+    assert_eq!(status, 400);
+    assert!(body.contains("illegal action"));
+
+    let (status, body) = request(&address, "GET", "/games/1", "");
+    assert_eq!(status, 200);
+    assert!(body.contains("\"turn\":1"));
+    assert_eq!(request(&address, "GET", "/games/7", "").0, 404);
+    assert_eq!(request(&address, "DELETE", "/games/1", "").0, 405);
+}
+
+#[test]
+fn engine_replies() {
+    let address = start();
+    let (status, body) = request(
+        &address,
+        "POST",
+        "/games",
+        "{\"engine\": \"1p\", \"playouts\": 100}",
+    );
+    assert_eq!(status, 201);
+    assert!(body.contains("\"turn\":1"));
+    assert!(body.contains("\"to_move\":\"2p\""));
+}
+
+#[test]
+fn searches_do_not_block_other_requests() {
+    let address = start();
+    let body = "{\"engine\": \"2p\", \"playouts\": 1000000}";
+    assert_eq!(request(&address, "POST", "/games", body).0, 201);
+    let search = {
+        let address = address.clone();
+        let action = "{\"place\": null, \"piece\": \"BSSF\"}";
+        thread::spawn(move || request(&address, "POST", "/games/1/actions", action))
+    };
+    // The action shows up before the engine has answered it.
+    let body = loop {
+        let (status, body) = request(&address, "GET", "/games/1", "");
+        assert_eq!(status, 200);
+        if body.contains("\"turn\":1") {
+            break body;
+        }
+    };
+    assert!(body.contains("\"to_move\":\"2p\""), "{}", body);
+    let (status, body) = request(
+        &address,
+        "POST",
+        "/games/1/actions",
+        "{\"place\": \"a1\", \"piece\": \"WCTH\"}",
+    );
+    assert_eq!(status, 400);
+    assert!(body.contains("the engine is to move"));
+    assert_eq!(request(&address, "POST", "/games", "").0, 201);
+    assert!(!search.is_finished());
+}
+
+#[test]
+fn idle_connections_time_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || serve_with_timeout(&listener, Duration::from_millis(200)));
+    let start = Instant::now();
+    let mut stream = TcpStream::connect(&address).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(response.starts_with("HTTP/1.1 400 "), "{}", response);
+    assert!(response.contains("failed to read the request"));
+}
+
+#[test]
+fn analyze_reports_every_action() {
+    let address = start();
+    let position = "BSSFWSSF../BSTH.../..../.... WCTH";
+    let body = format!("{{\"position\": \"{}\", \"playouts\": 2000}}", position);
+    let (status, body) = request(&address, "POST", "/analyze", &body);
+    assert_eq!(status, 200);
+    let state = State::from_notation(position).unwrap();
+    assert_eq!(
+        body.matches("\"visits\"").count(),
+        state.legal_actions().len()
+    );
+
+    let (status, body) = request(&address, "POST", "/analyze", "{\"position\": 3}");
+    assert_eq!(status, 400);
+    assert!(body.contains("`position` must be a string"));
+    assert_eq!(request(&address, "POST", "/analyze", "{").0, 400);
+}
@@ -0,0 +1,104 @@
+use quarto::agents::{mcts_rave_action, primitive_monte_carlo_action, random_action};
+use quarto::montecarlo::{primitive_monte_carlo_search, RAVE_EQUIVALENCE};
+use quarto::quarto::{Action, State};
+use quarto::rng;
+use std::collections::HashSet;
+
+const POSITIONS: [&str; 3] = [
+    "..../..../..../.... BSSF",
+    "BSSFWCTHBCSH./..BSTHWCSF/.BCTFWSTF./WSSH... BCTH",
+    "BCSH..WCSH/WSTHBCSFBCTFBSTF/.BSSF../WCTFBCTHBSSHWCTH WSTF",
+];
+
+#[test]
+fn every_legal_action_is_visited() {
+    rng::seed(1);
+    for notation in POSITIONS {
+        let state = State::from_notation(notation).unwrap();
+        let legal: HashSet<Action> = state.legal_actions().into_iter().collect();
+        // Fewer playouts than actions as well as enough for several rounds.
+        for playout_number in [1, 50, 2000] {
+            let statistics = primitive_monte_carlo_search(&state, playout_number);
+            assert_eq!(statistics.len(), legal.len(), "{}", notation);
+            for s in &statistics {
+                assert!(legal.contains(&s.action), "{:?} in {}", s.action, notation);
+                assert!(s.trials > 0, "{:?} in {}", s.action, notation);
+                assert!((0.0..=1.0).contains(&s.value));
+            }
+        }
+    }
+}
+
+#[test]
+fn playouts_stay_within_the_budget() {
+    rng::seed(2);
+    let state = State::from_notation(POSITIONS[1]).unwrap();
+    let actions = state.legal_actions().len();
+    assert!(actions < 1000);
+    let trials: i32 = primitive_monte_carlo_search(&state, 1000)
+        .iter()
+        .map(|s| s.trials)
+        .sum();
+    assert!(trials <= 1000, "{}", trials);
+    assert!(trials >= 500, "{}", trials);
+}
+
+#[test]
+fn winning_placements_are_taken() {
+    rng::seed(3);
+    let state = State::from_notation("BSTFBSTHBSSF./..../..../.... BCSH").unwrap();
+    let statistics = primitive_monte_carlo_search(&state, 1000);
+    let win = statistics
+        .iter()
+        .find(|s| s.action == (Some((0, 3)), None))
+        .unwrap();
+    assert_eq!(win.value, 1.0);
+    assert_eq!(
+        primitive_monte_carlo_action(&state, 1000),
+        (Some((0, 3)), None)
+    );
+}
+
+#[test]
+fn rave_takes_winning_placements() {
+    rng::seed(4);
+    let state = State::from_notation("BSTFBSTHBSSF./..../..../.... BCSH").unwrap();
+    assert_eq!(
+        mcts_rave_action(&state, 1000, RAVE_EQUIVALENCE),
+        (Some((0, 3)), None)
+    );
+}
+
+#[test]
+fn rave_beats_random_play() {
+    rng::seed(5);
+    let mut score = 0.0;
+    for game in 0..10 {
+        let mut state = State::new();
+        while !state.is_done() {
+            let action = if state.is_first_player() == (game % 2 == 0) {
+                mcts_rave_action(&state, 300, RAVE_EQUIVALENCE)
+            } else {
+                random_action(&state)
+            };
+            state.apply_action(action);
+        }
+        let first_player_score = state.get_first_player_score_for_win_rate();
+        score += if game % 2 == 0 {
+            first_player_score
+        } else {
+            1.0 - first_player_score
+        };
+    }
+    assert!(score >= 8.0, "{}", score);
+}
+
+#[test]
+#[should_panic(expected = "no action is left in a finished game")]
+fn finished_games_have_no_action() {
+    let mut state = State::from_notation("BSTFBSTHBSSF./..../..../.... BCSH").unwrap();
+    state.apply_action((Some((0, 3)), None));
+    assert!(state.is_done());
+    assert!(primitive_monte_carlo_search(&state, 100).is_empty());
+    primitive_monte_carlo_action(&state, 100);
+}
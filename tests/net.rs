@@ -0,0 +1,134 @@
+use quarto::agents::{random_action, ActionFn};
+use quarto::net::{connect, serve, serve_with_timeout, Connection};
+use quarto::quarto::State;
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+fn listen() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    (listener, address)
+}
+
+// Sends what a client logs to the test, which waits on it for the welcome.
+struct Log(mpsc::Sender<String>);
+
+impl Write for Log {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = self.0.send(String::from_utf8_lossy(buf).into_owned());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn random_players_finish_a_game() {
+    let (listener, address) = listen();
+    let server = thread::spawn(move || serve(&listener, &mut io::sink()).unwrap());
+    let clients: Vec<_> = (0..2)
+        .map(|i| {
+            let address = address.clone();
+            let (sender, receiver) = mpsc::channel();
+            let client = thread::spawn(move || {
+                let mut player: ActionFn = random_action;
+                let mut log = Log(sender);
+                connect(
+                    address.as_str(),
+                    &format!("random{}", i),
+                    &mut player,
+                    &mut log,
+                )
+                .unwrap()
+            });
+            // The second client connects once the first has been accepted.
+            let mut logged = String::new();
+            while !logged.contains('\n') {
+                logged.push_str(&receiver.recv().unwrap());
+            }
+            assert_eq!(logged, format!("playing as {}p\n", i + 1));
+            client
+        })
+        .collect();
+    let (record, outcome) = server.join().unwrap();
+    let state = record.final_state();
+    assert!(state.is_done());
+    assert_eq!(outcome.winner.is_none(), !state.can_win());
+    for client in clients {
+        let client_outcome = client.join().unwrap();
+        assert_eq!(client_outcome.winner, outcome.winner);
+        assert_eq!(client_outcome.reason, outcome.reason);
+    }
+}
+
+#[test]
+fn silent_players_time_out() {
+    let (listener, address) = listen();
+    let server =
+        thread::spawn(move || serve_with_timeout(&listener, TIMEOUT, &mut io::sink()).unwrap());
+    let mut silent = Connection::new(TcpStream::connect(&address).unwrap()).unwrap();
+    assert_eq!(
+        silent.receive().unwrap().unwrap(),
+        "error timed out waiting for hello"
+    );
+    assert_eq!(silent.receive().unwrap(), None);
+
+    let mut first = Connection::new(TcpStream::connect(&address).unwrap()).unwrap();
+    first.send("hello quarto/1 first").unwrap();
+    assert_eq!(first.receive().unwrap().unwrap(), "welcome 1p");
+    let mut second = Connection::new(TcpStream::connect(&address).unwrap()).unwrap();
+    second.send("hello quarto/1 second").unwrap();
+    assert_eq!(second.receive().unwrap().unwrap(), "welcome 2p");
+    assert_eq!(
+        first.receive().unwrap().unwrap(),
+        format!("position {}", State::new().notation())
+    );
+    assert_eq!(first.receive().unwrap().unwrap(), "go");
+    // The first player never answers.
+    let (record, outcome) = server.join().unwrap();
+    assert_eq!(first.receive().unwrap().unwrap(), "result 2p time");
+    assert_eq!(second.receive().unwrap().unwrap(), "result 2p time");
+    assert!(record.actions.is_empty());
+    assert_eq!(record.resigned, Some(0));
+    assert_eq!(outcome.winner, Some(1));
+    assert_eq!(outcome.reason, "time");
+}
+
+#[test]
+fn illegal_action_forfeits() {
+    let (listener, address) = listen();
+    let client = thread::spawn(move || {
+        let mut first = Connection::new(TcpStream::connect(&address).unwrap()).unwrap();
+        first.send("hello quarto/1 first").unwrap();
+        let mut second = Connection::new(TcpStream::connect(&address).unwrap()).unwrap();
+        second.send("hello quarto/1 second").unwrap();
+        assert_eq!(first.receive().unwrap().unwrap(), "welcome 1p");
+        assert_eq!(second.receive().unwrap().unwrap(), "welcome 2p");
+        assert_eq!(
+            first.receive().unwrap().unwrap(),
+            format!("position {}", State::new().notation())
+        );
+        assert_eq!(first.receive().unwrap().unwrap(), "go");
+        // Nothing can be placed on the first turn.
+        first.send("action a1 BSSF").unwrap();
+        assert_eq!(first.receive().unwrap().unwrap(), "result 2p illegal");
+        assert_eq!(second.receive().unwrap().unwrap(), "result 2p illegal");
+    });
+    let mut log = Vec::new();
+    let (record, outcome) = serve(&listener, &mut log).unwrap();
+    client.join().unwrap();
+    assert_eq!(
+        String::from_utf8(log).unwrap(),
+        "first joined as 1p\nsecond joined as 2p\nresult 2p illegal\n"
+    );
+    assert!(record.actions.is_empty());
+    assert_eq!(record.resigned, Some(0));
+    assert_eq!(outcome.winner, Some(1));
+}
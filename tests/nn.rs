@@ -0,0 +1,76 @@
+use quarto::nn::{
+    action_index, encode, index_action, puct_action, Network, INPUT_SIZE, POLICY_SIZE,
+};
+use quarto::quarto::{Piece, State};
+use quarto::rng;
+
+fn path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("quarto-nn-{}-{}.bin", std::process::id(), name))
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn positions_are_encoded() {
+    let state = State::from_notation("BSTF.../..../..../.... WCSH").unwrap();
+    let input = encode(&state);
+    assert_eq!(input.len(), INPUT_SIZE);
+    // BSTF has every attribute bit clear, so it is marked in the second plane of each attribute.
+    for i in 0..4 {
+        assert_eq!(input[2 * i * 16], 0.0);
+        assert_eq!(input[(2 * i + 1) * 16], 1.0);
+    }
+    assert_eq!(input[..128].iter().sum::<f32>(), 4.0);
+    let hand = &input[128..144];
+    assert_eq!(hand.iter().sum::<f32>(), 1.0);
+    assert_eq!(hand["WCSH".parse::<Piece>().unwrap().to_index()], 1.0);
+    assert_eq!(input[144..].iter().sum::<f32>(), 14.0);
+}
+
+#[test]
+fn actions_are_indexed() {
+    for index in 0..POLICY_SIZE {
+        assert_eq!(action_index(index_action(index)), index);
+    }
+    assert_eq!(index_action(POLICY_SIZE - 1), (None, None));
+}
+
+#[test]
+fn priors_cover_the_legal_actions() {
+    let network = Network::random(&[32]);
+    let state = State::from_notation("BSSF.../..../..../.... WCTH").unwrap();
+    let (priors, value) = network.evaluate(&state);
+    assert_eq!(priors.len(), state.legal_actions().len());
+    assert!((priors.iter().map(|&(_, p)| p).sum::<f32>() - 1.0).abs() < 1e-4);
+    assert!((0.0..=1.0).contains(&value));
+}
+
+#[test]
+fn weights_round_trip() {
+    let network = Network::random(&[24, 16]);
+    let weights = path("weights");
+    network.save(&weights).unwrap();
+    assert_eq!(Network::load(&weights).unwrap(), network);
+
+    let truncated = path("truncated");
+    let bytes = std::fs::read(&weights).unwrap();
+    std::fs::write(&truncated, &bytes[..bytes.len() - 4]).unwrap();
+    assert!(Network::load(&truncated).is_err());
+    std::fs::write(&truncated, b"not a network").unwrap();
+    assert!(Network::load(&truncated).is_err());
+    std::fs::remove_file(weights).unwrap();
+    std::fs::remove_file(truncated).unwrap();
+}
+
+#[test]
+fn puct_finds_wins() {
+    // Even an untrained network leads the search to a completed line.
+    rng::seed(1);
+    let network = Network::random(&[32]);
+    let state = State::from_notation("BSSFBSSHBSTF./..../..../.... BSTH").unwrap();
+    let action = puct_action(&state, &network, 1000);
+    assert!(state.is_legal_action(action));
+    assert_eq!(action, (Some((0, 3)), None));
+}
@@ -0,0 +1,126 @@
+use quarto::opening_book::OpeningBook;
+use quarto::quarto::{Piece, State, Symmetry};
+use quarto::rng;
+
+fn path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("quarto-book-{}-{}.txt", std::process::id(), name))
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+const NOTATION: &str = "BSSFWCTH../..../..../.... BCSH";
+
+#[test]
+fn symmetries_map_actions_back() {
+    let state = State::from_notation(NOTATION).unwrap();
+    for symmetry in Symmetry::all().iter().step_by(7) {
+        let image = state.transformed(symmetry);
+        assert_eq!(
+            image.canonical(Symmetry::all()).0,
+            state.canonical(Symmetry::all()).0
+        );
+        for action in state.legal_actions() {
+            let (place, piece) = action;
+            let mapped = (
+                place.map(|place| symmetry.map_place(place)),
+                piece.map(|piece| symmetry.map_piece(piece)),
+            );
+            assert_eq!(symmetry.unmap_action(mapped), action);
+            assert!(image.is_legal_action(mapped));
+            let mut next_state = state;
+            next_state.apply_action(action);
+            let mut next_image = image;
+            next_image.apply_action(mapped);
+            assert_eq!(next_image, next_state.transformed(symmetry));
+        }
+    }
+}
+
+#[test]
+fn book_moves_survive_symmetries() {
+    let state = State::from_notation(NOTATION).unwrap();
+    let (key, symmetry) = state.canonical(Symmetry::all());
+    let canonical_state = state.transformed(&symmetry);
+    let (h, w) = canonical_state.legal_placements()[0];
+    let piece: Piece = canonical_state.legal_pieces()[0];
+    let path = path("symmetries");
+    std::fs::write(
+        &path,
+        format!(
+            "# quarto opening book v1\n{:x} {},{} {} 1\n",
+            key, h, w, piece
+        ),
+    )
+    .unwrap();
+    let book = OpeningBook::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let mut expected = canonical_state;
+    expected.apply_action((Some((h, w)), Some(piece)));
+    let expected = expected.canonical(Symmetry::all()).0;
+    rng::seed(1);
+    for symmetry in Symmetry::all().iter().step_by(101) {
+        let image = state.transformed(symmetry);
+        // Looked up twice to go through the cached canonical key as well.
+        for _ in 0..2 {
+            let action = book.lookup(&image).unwrap();
+            assert!(image.is_legal_action(action));
+            let mut next_state = image;
+            next_state.apply_action(action);
+            assert_eq!(next_state.canonical(Symmetry::all()).0, expected);
+        }
+    }
+    let mut other = state;
+    other.apply_action((Some((3, 3)), Some(State::new().legal_pieces()[15])));
+    assert_eq!(book.lookup(&other), None);
+}
+
+#[test]
+fn illegal_book_moves_are_skipped() {
+    let state = State::from_notation(NOTATION).unwrap();
+    let (key, symmetry) = state.canonical(Symmetry::all());
+    let canonical_state = state.transformed(&symmetry);
+    let taken = (0..4)
+        .flat_map(|h| (0..4).map(move |w| (h, w)))
+        .find(|&(h, w)| canonical_state.get_piece(h, w).is_some())
+        .unwrap();
+    let (h, w) = canonical_state.legal_placements()[0];
+    let piece: Piece = canonical_state.legal_pieces()[0];
+    let illegal = format!("{},{} {} 100 9,9 {} 100", taken.0, taken.1, piece, piece);
+    let path = path("illegal");
+    for (moves, found) in [
+        (illegal.clone(), false),
+        (format!("{} {},{} {} 1", illegal, h, w, piece), true),
+    ] {
+        std::fs::write(
+            &path,
+            format!("# quarto opening book v1\n{:x} {}\n", key, moves),
+        )
+        .unwrap();
+        let book = OpeningBook::load(&path).unwrap();
+        for _ in 0..10 {
+            let action = book.lookup(&state);
+            assert_eq!(action.is_some(), found);
+            if let Some(action) = action {
+                assert!(state.is_legal_action(action));
+            }
+        }
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn generated_books_report_progress() {
+    rng::seed(2);
+    let mut plies = Vec::new();
+    let book = OpeningBook::generate(2, 50, &mut |ply, positions| plies.push((ply, positions)));
+    assert_eq!(plies.len(), 2);
+    assert_eq!(plies[0], (0, 1));
+    assert_eq!(plies[1].1, book.len());
+    let path = path("generated");
+    book.save(&path).unwrap();
+    assert_eq!(OpeningBook::load(&path).unwrap().len(), book.len());
+    std::fs::remove_file(&path).unwrap();
+}
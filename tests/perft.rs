@@ -0,0 +1,142 @@
+use quarto::perft::{perft, perft_unique, PerftCounts};
+use quarto::quarto::{GameState, State, Symmetry};
+
+fn nodes(counts: &[PerftCounts]) -> Vec<u64> {
+    counts.iter().map(|counts| counts.nodes).collect()
+}
+
+fn counts(rows: &[(u64, u64, u64)]) -> Vec<PerftCounts> {
+    rows.iter()
+        .map(|&(nodes, wins, draws)| PerftCounts { nodes, wins, draws })
+        .collect()
+}
+
+// Move generation from placements and pieces alone, with wins found by scanning the whole board.
+fn reference<const N: usize, const K: usize>(state: &GameState<N, K>, counts: &mut [PerftCounts]) {
+    let Some((first, rest)) = counts.split_first_mut() else {
+        return;
+    };
+    let placements = if state.is_first_turn() {
+        vec![None]
+    } else {
+        state.legal_placements().into_iter().map(Some).collect()
+    };
+    for place in placements {
+        let mut placed = *state;
+        if let Some((h, w)) = place {
+            placed.put_piece(h, w);
+        }
+        let wins = !placed.winning_lines().is_empty();
+        let full = placed.empty_count() == 0 || placed.legal_pieces().is_empty();
+        if wins || full {
+            first.nodes += 1;
+            if wins {
+                first.wins += 1;
+            } else {
+                first.draws += 1;
+            }
+            continue;
+        }
+        for piece in placed.legal_pieces() {
+            let mut next_state = placed;
+            next_state.select_piece(piece);
+            first.nodes += 1;
+            reference(&next_state, rest);
+        }
+    }
+}
+
+fn reference_perft<const N: usize, const K: usize>(
+    state: &GameState<N, K>,
+    depth: usize,
+) -> Vec<PerftCounts> {
+    let mut counts = vec![PerftCounts::default(); depth];
+    reference(state, &mut counts);
+    counts
+}
+
+// Eight empty cells and no placement of the piece in hand wins yet.
+const MIDGAME: &str = "BSSFWCTHBCSH./..BSTHWCSF/.BCTFWSTF./WSSH... BCTH";
+// Five empty cells, so every game is over after at most five actions.
+const ENDGAME: &str = "BCSH..WCSH/WSTHBCSFBCTFBSTF/.BSSF../WCTFBCTHBSSHWCTH WSTF";
+
+#[test]
+fn initial_position() {
+    // 16 pieces to hand over, then 16 cells times 15 pieces, then 15 cells times 14 pieces.
+    assert_eq!(
+        perft(&State::new(), 3),
+        counts(&[(16, 0, 0), (3840, 0, 0), (806400, 0, 0)])
+    );
+    assert_eq!(
+        nodes(&perft(&GameState::<3, 3>::new(), 3)),
+        [8, 8 * 9 * 7, 8 * 9 * 7 * 8 * 6]
+    );
+}
+
+#[test]
+fn fixed_positions() {
+    let midgame = State::from_notation(MIDGAME).unwrap();
+    assert_eq!(
+        perft(&midgame, 3),
+        counts(&[(56, 0, 0), (2272, 16, 0), (61792, 1472, 0)])
+    );
+    let endgame = State::from_notation(ENDGAME).unwrap();
+    assert_eq!(
+        perft(&endgame, 6),
+        counts(&[
+            (20, 0, 0),
+            (224, 8, 0),
+            (1144, 152, 0),
+            (1984, 952, 0),
+            (1032, 552, 480),
+            (0, 0, 0),
+        ])
+    );
+    // Three placed pieces can complete a line on the 3×3 board.
+    assert_eq!(
+        perft(&GameState::<3, 3>::new(), 4)[3],
+        PerftCounts {
+            nodes: 819072,
+            wins: 6912,
+            draws: 0
+        }
+    );
+}
+
+#[test]
+fn agrees_with_the_reference() {
+    assert_eq!(perft(&State::new(), 2), reference_perft(&State::new(), 2));
+    for notation in [MIDGAME, ENDGAME] {
+        let state = State::from_notation(notation).unwrap();
+        assert_eq!(perft(&state, 3), reference_perft(&state, 3), "{}", notation);
+    }
+    let endgame = State::from_notation(ENDGAME).unwrap();
+    assert_eq!(perft(&endgame, 6), reference_perft(&endgame, 6));
+    let small = GameState::<3, 3>::new();
+    assert_eq!(perft(&small, 4), reference_perft(&small, 4));
+}
+
+#[test]
+fn symmetry_reduced() {
+    // Under the board symmetries corners and centers form one orbit of cells and edges another.
+    assert_eq!(
+        nodes(&perft_unique(
+            &State::new(),
+            3,
+            Symmetry::board_symmetries()
+        )),
+        [16, 2 * 16 * 15, 20160]
+    );
+    // Every first piece is alike once attributes may be renamed. The second action takes one of
+    // the two kinds of cell and a piece sharing zero to three attributes with the first.
+    assert_eq!(
+        nodes(&perft_unique(&State::new(), 2, Symmetry::all())),
+        [1, 8]
+    );
+    let endgame = State::from_notation(ENDGAME).unwrap();
+    let unique = perft_unique(&endgame, 5, Symmetry::board_symmetries());
+    for (unique, all) in unique.iter().zip(perft(&endgame, 5)) {
+        assert!(unique.nodes <= all.nodes);
+        assert!(unique.wins <= all.wins && unique.draws <= all.draws);
+    }
+}
@@ -0,0 +1,59 @@
+use quarto::play::{replay, GameRecord};
+use quarto::quarto::parse_action;
+use std::io;
+
+fn path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("quarto-play-{}-{}.txt", std::process::id(), name))
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn replays_show_every_position() {
+    let record = GameRecord {
+        actions: ["- BSTF", "a1 WCSH", "b2 BCTH"]
+            .iter()
+            .map(|action| parse_action(action).unwrap())
+            .collect(),
+        resigned: Some(0),
+        ..GameRecord::new()
+    };
+    let replay = replay(&record);
+    // The initial position and one board per action, each closed by a separator.
+    let separators = replay
+        .lines()
+        .filter(|line| line.starts_with("  +"))
+        .count();
+    assert_eq!(separators, 4 * 5);
+    let moves: Vec<&str> = replay.lines().filter(|line| line.contains(". ")).collect();
+    assert_eq!(moves, ["1. 1p: - BSTF", "2. 2p: a1 WCSH", "3. 1p: b2 BCTH"]);
+    assert!(replay.contains("in hand: BCTH\n"));
+    assert!(replay.ends_with("1p resigned\n"));
+}
+
+#[test]
+fn records_with_illegal_actions_are_rejected() {
+    let path = path("illegal");
+    for (actions, message) in [
+        ("- BSTF\nz9 WCSH\n", "illegal action at ply 2: z9 WCSH"),
+        (
+            "- BSTF\na1 WCSH\nb2 BSTF\n",
+            "illegal action at ply 3: b2 BSTF",
+        ),
+        (
+            "- BSTF\na1 WCSH\na1 BCTH\n",
+            "illegal action at ply 3: a1 BCTH",
+        ),
+    ] {
+        std::fs::write(&path, format!("# quarto game v1\n{}", actions)).unwrap();
+        let error = GameRecord::load(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), message);
+    }
+    std::fs::write(&path, "# quarto game v1\n- BSTF\na1 WCSH\nb2 BCTH\n").unwrap();
+    let loaded = GameRecord::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap().actions.len(), 3);
+}
@@ -0,0 +1,290 @@
+use proptest::prelude::*;
+use proptest::sample::Index;
+use proptest::strategy::ValueTree;
+use proptest::test_runner::TestRunner;
+use quarto::agents::{mcts_action, mcts_rave_action, primitive_monte_carlo_action, random_action};
+use quarto::montecarlo::RAVE_EQUIVALENCE;
+use quarto::quarto::{Action, Objective, Piece, RuleSet, Squares, State};
+use std::collections::HashSet;
+
+#[derive(Clone, Copy, Debug)]
+enum Agent {
+    Random,
+    Mcts(usize),
+    Rave(usize),
+    Primitive(usize),
+}
+
+impl Agent {
+    fn action(self, state: &State) -> Action {
+        match self {
+            Agent::Random => random_action(state),
+            Agent::Mcts(playouts) => mcts_action(state, playouts),
+            Agent::Rave(playouts) => mcts_rave_action(state, playouts, RAVE_EQUIVALENCE),
+            Agent::Primitive(playouts) => primitive_monte_carlo_action(state, playouts),
+        }
+    }
+}
+
+fn rules() -> impl Strategy<Value = RuleSet> {
+    (
+        prop_oneof![
+            Just(Squares::Off),
+            Just(Squares::Blocks),
+            Just(Squares::Toroidal)
+        ],
+        any::<bool>(),
+        prop_oneof![
+            Just(Objective::Win),
+            Just(Objective::Misere),
+            Just(Objective::Scored)
+        ],
+    )
+        .prop_map(|(squares, quarto_call, objective)| RuleSet {
+            squares,
+            quarto_call,
+            objective,
+        })
+        .prop_filter(
+            "misere is not played with the call rule",
+            RuleSet::is_playable,
+        )
+}
+
+fn search_agent() -> impl Strategy<Value = Agent> {
+    prop_oneof![
+        (1..60usize).prop_map(Agent::Mcts),
+        (1..60usize).prop_map(Agent::Rave),
+        (1..60usize).prop_map(Agent::Primitive),
+    ]
+}
+
+// What the reference knows of the last action: whether it won the game and whether it left a
+// line open to claim.
+#[derive(Clone, Copy, Default)]
+struct Reference {
+    won: bool,
+    open: bool,
+}
+
+impl Reference {
+    // Follows an action that was applied to `state`.
+    fn update(&mut self, state: &State, (place, piece): Action) {
+        let Some((h, w)) = place else {
+            if piece.is_none() {
+                // A claim wins exactly when a line is open.
+                *self = Reference {
+                    won: self.open,
+                    open: false,
+                };
+            }
+            return;
+        };
+        let line = patterns(state)
+            .into_iter()
+            .any(|cells| cells.contains(&(h, w)) && completed(state, cells));
+        *self = if state.rules().quarto_call && piece.is_some() {
+            Reference {
+                won: false,
+                open: line,
+            }
+        } else {
+            Reference {
+                won: line,
+                open: false,
+            }
+        };
+    }
+}
+
+// Plays the chosen legal actions from the initial position, claims and missed calls included.
+fn start(rules: RuleSet, choices: &[Index]) -> (State, Reference) {
+    let mut state = State::new().with_rules(rules);
+    let mut reference = Reference::default();
+    for choice in choices {
+        if state.is_done() {
+            break;
+        }
+        let action = *choice.get(&state.legal_actions());
+        state.apply_action(action);
+        reference.update(&state, action);
+        check_state(&state, reference);
+    }
+    (state, reference)
+}
+
+// Whether four cells hold pieces that all share an attribute, checked one cell at a time.
+fn completed(state: &State, cells: [(usize, usize); 4]) -> bool {
+    let pieces: Vec<Piece> = cells
+        .iter()
+        .filter_map(|&(h, w)| state.get_piece(h, w))
+        .collect();
+    pieces.len() == 4
+        && (0..4).any(|i| {
+            pieces
+                .iter()
+                .all(|piece| piece.attribute(i) == pieces[0].attribute(i))
+        })
+}
+
+// The winning patterns, written out without the ones `State` generates.
+fn patterns(state: &State) -> Vec<[(usize, usize); 4]> {
+    let mut patterns = Vec::new();
+    for i in 0..4 {
+        patterns.push([(i, 0), (i, 1), (i, 2), (i, 3)]);
+        patterns.push([(0, i), (1, i), (2, i), (3, i)]);
+    }
+    patterns.push([(0, 0), (1, 1), (2, 2), (3, 3)]);
+    patterns.push([(0, 3), (1, 2), (2, 1), (3, 0)]);
+    let corners = match state.rules().squares {
+        Squares::Off => 0,
+        Squares::Blocks => 3,
+        Squares::Toroidal => 4,
+    };
+    for h in 0..corners {
+        for w in 0..corners {
+            let (h2, w2) = ((h + 1) % 4, (w + 1) % 4);
+            patterns.push([(h, w), (h, w2), (h2, w), (h2, w2)]);
+        }
+    }
+    patterns
+}
+
+// A slow reference for `is_done`.
+fn reference_is_done(state: &State, reference: Reference) -> bool {
+    if reference.won {
+        return true;
+    }
+    let empty = (0..4)
+        .flat_map(|h| (0..4).map(move |w| (h, w)))
+        .any(|(h, w)| state.get_piece(h, w).is_none());
+    !empty || (state.selected_piece().is_none() && state.legal_pieces().is_empty())
+}
+
+fn check_state(state: &State, reference: Reference) {
+    let placed: Vec<Piece> = (0..4)
+        .flat_map(|h| (0..4).map(move |w| (h, w)))
+        .filter_map(|(h, w)| state.get_piece(h, w))
+        .collect();
+    let in_hand = usize::from(state.selected_piece().is_some());
+    let unused = state.legal_pieces();
+    let notation = state.notation();
+
+    assert_eq!(placed.len() + in_hand + unused.len(), 16, "{}", notation);
+    assert_eq!(state.turn(), placed.len() + in_hand, "{}", notation);
+    assert_eq!(state.empty_count(), 16 - placed.len(), "{}", notation);
+
+    let mut seen = HashSet::new();
+    for piece in placed.iter().chain(state.selected_piece().iter()) {
+        assert!(seen.insert(*piece), "{} is used twice: {}", piece, notation);
+        assert!(!unused.contains(piece), "{} is used: {}", piece, notation);
+    }
+
+    assert_eq!(state.can_win(), reference.won, "{}", notation);
+    assert_eq!(state.can_claim(), reference.open, "{}", notation);
+    assert_eq!(
+        state.is_done(),
+        reference_is_done(state, reference),
+        "{}",
+        notation
+    );
+    assert_eq!(
+        State::from_notation(&notation),
+        Some(*state),
+        "{}",
+        notation
+    );
+}
+
+fn check_action(state: &State, agent: Agent, (place, piece): Action) {
+    let context = format!("{:?} in {}", agent, state.notation());
+    if (place, piece) == (None, None) {
+        assert!(
+            state.can_claim(),
+            "a claim without an open line: {}",
+            context
+        );
+        assert!(state.is_legal_action((place, piece)), "{}", context);
+        return;
+    }
+    if state.is_first_turn() {
+        assert_eq!(place, None, "{}", context);
+    } else {
+        let place = place.unwrap_or_else(|| panic!("no placement: {}", context));
+        assert!(state.legal_placements().contains(&place), "{}", context);
+    }
+    match (place, piece) {
+        (Some((h, w)), Some(piece)) => {
+            assert!(state.legal_pieces().contains(&piece), "{}", context);
+            // Completing a line and handing over a piece is a missed call.
+            if state.can_put_then_win(h, w) {
+                assert!(state.rules().quarto_call, "{}", context);
+            }
+        }
+        (None, Some(piece)) => assert!(state.legal_pieces().contains(&piece), "{}", context),
+        // Only a placement that ends the game comes without a piece.
+        (Some((h, w)), None) => assert!(
+            state.is_last_turn() || state.can_put_then_win(h, w),
+            "{}",
+            context
+        ),
+        (None, None) => unreachable!(),
+    }
+    assert!(state.is_legal_action((place, piece)), "{}", context);
+}
+
+fn play((mut state, mut reference): (State, Reference), agents: [Agent; 2]) {
+    let mut mover = 0;
+    while !state.is_done() {
+        let action = agents[mover].action(&state);
+        check_action(&state, agents[mover], action);
+        state.apply_action(action);
+        reference.update(&state, action);
+        check_state(&state, reference);
+        mover ^= 1;
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn random_games(rules in rules(), choices in prop::collection::vec(any::<Index>(), 0..16)) {
+        play(start(rules, &choices), [Agent::Random, Agent::Random]);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(200))]
+
+    #[test]
+    fn search_games(
+        rules in rules(),
+        choices in prop::collection::vec(any::<Index>(), 0..16),
+        search in search_agent(),
+        search_first in any::<bool>(),
+    ) {
+        let agents = if search_first {
+            [search, Agent::Random]
+        } else {
+            [Agent::Random, search]
+        };
+        play(start(rules, &choices), agents);
+    }
+}
+
+#[test]
+fn starts_include_claims_and_open_lines() {
+    let mut runner = TestRunner::deterministic();
+    let strategy = (rules(), prop::collection::vec(any::<Index>(), 0..16));
+    let (mut open, mut claimed) = (0, 0);
+    for _ in 0..2000 {
+        let (rules, choices) = strategy.new_tree(&mut runner).unwrap().current();
+        let (state, reference) = start(rules, &choices);
+        open += usize::from(reference.open);
+        claimed +=
+            usize::from(state.is_done() && state.selected_piece().is_some() && reference.won);
+    }
+    assert!(open > 0);
+    assert!(claimed > 0);
+}
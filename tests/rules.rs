@@ -0,0 +1,18 @@
+use quarto::agents::random_action;
+use quarto::quarto::State;
+
+#[test]
+fn notation_round_trip() {
+    let mut state = State::new();
+    while !state.is_done() {
+        let notation = state.notation();
+        let parsed = State::from_notation(&notation).unwrap();
+        assert_eq!(parsed.notation(), notation);
+        assert_eq!(parsed.turn(), state.turn());
+        assert_eq!(parsed.is_first_player(), state.is_first_player());
+        state.apply_action(random_action(&state));
+    }
+    assert!(State::from_notation("..../..../..../.... -").is_some());
+    assert!(State::from_notation("BSSF.../..../..../.... BSSF").is_none());
+    assert!(State::from_notation("BSSF.../..../..../.... -").is_none());
+}
@@ -0,0 +1,60 @@
+use quarto::nn::{index_action, POLICY_SIZE};
+use quarto::selfplay::{generate, load, save};
+
+fn path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!(
+            "quarto-selfplay-{}-{}.bin",
+            std::process::id(),
+            name
+        ))
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn samples_do_not_depend_on_threads() {
+    assert_eq!(generate(4, 50, 1, 7), generate(4, 50, 3, 7));
+    assert_ne!(generate(4, 50, 2, 7), generate(4, 50, 2, 8));
+}
+
+#[test]
+fn samples_describe_the_games() {
+    let samples = generate(3, 100, 2, 1);
+    for pair in samples.windows(2) {
+        let [sample, next] = pair else { unreachable!() };
+        if sample.game == next.game {
+            assert_eq!(next.ply, sample.ply + 1);
+            // The player to move changes after every action.
+            assert_eq!(sample.outcome + next.outcome, 1.0);
+        } else {
+            assert_eq!(next.game, sample.game + 1);
+            assert_eq!(next.ply, 0);
+        }
+    }
+    for sample in &samples {
+        assert_eq!(sample.policy.len(), POLICY_SIZE);
+        assert!((sample.policy.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!([0.0, 0.5, 1.0].contains(&sample.outcome));
+        if sample.ply == 0 {
+            // Only pieces are chosen on the first turn.
+            for (index, &p) in sample.policy.iter().enumerate() {
+                if p > 0.0 {
+                    assert!(matches!(index_action(index), (None, Some(_))));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn samples_round_trip() {
+    let samples = generate(2, 50, 2, 3);
+    let path = path("round-trip");
+    save(&samples, &path).unwrap();
+    assert_eq!(load(&path).unwrap(), samples);
+    std::fs::write(&path, b"QNET").unwrap();
+    assert!(load(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,120 @@
+#![cfg(feature = "serde")]
+
+use quarto::agents::{random_action, ActionFn};
+use quarto::analysis::Analysis;
+use quarto::montecarlo::mcts_search;
+use quarto::play::GameRecord;
+use quarto::quarto::{Action, GameState, Piece, RuleSet, State};
+use quarto::r#match::{play_match_game, GameOutcome};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+
+fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: &T) {
+    let json = serde_json::to_string(value).unwrap();
+    assert_eq!(&serde_json::from_str::<T>(&json).unwrap(), value);
+    let bytes = bincode::serialize(value).unwrap();
+    assert_eq!(&bincode::deserialize::<T>(&bytes).unwrap(), value);
+}
+
+fn random_game() -> (GameRecord, GameOutcome) {
+    let mut first: ActionFn = random_action;
+    let mut second: ActionFn = random_action;
+    play_match_game([&mut first, &mut second])
+}
+
+#[test]
+fn pieces() {
+    for idx in 0..16 {
+        round_trip(&Piece::from_index(idx));
+    }
+    let piece: Piece = "WSTH".parse().unwrap();
+    assert_eq!(serde_json::to_string(&piece).unwrap(), "\"WSTH\"");
+    assert_eq!(bincode::serialize(&piece).unwrap(), [9]);
+    assert!(serde_json::from_str::<Piece>("\"XCSH\"").is_err());
+    assert!(bincode::deserialize::<Piece>(&[64]).is_err());
+}
+
+#[test]
+fn states_and_actions() {
+    let (record, _) = random_game();
+    let mut state = State::new();
+    round_trip(&state);
+    for &action in &record.actions {
+        round_trip(&action);
+        state.apply_action(action);
+        round_trip(&state);
+    }
+    let action: Action = (Some((1, 3)), Some("BSSF".parse().unwrap()));
+    assert_eq!(serde_json::to_string(&action).unwrap(), "[[1,3],\"BSSF\"]");
+    assert!(serde_json::from_str::<State>("\"BSSF.../..../..../.... BSSF\"").is_err());
+    assert!(bincode::deserialize::<State>(&[1; 17]).is_err());
+}
+
+#[test]
+fn states_keep_their_rules() {
+    for spec in ["misere", "squares,call", "toroidal,call,scored"] {
+        let rules: RuleSet = spec.parse().unwrap();
+        let mut state = State::new().with_rules(rules);
+        round_trip(&state);
+        while !state.is_done() {
+            state.apply_action(random_action(&state));
+            round_trip(&state);
+        }
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.contains(spec), "{}", json);
+        let bytes = bincode::serialize(&state).unwrap();
+        assert_eq!(
+            bincode::deserialize::<State>(&bytes).unwrap().rules(),
+            rules
+        );
+    }
+
+    let calling = "BSSFBSSHBSTF./..../..../.... BSTH call";
+    let mut state = State::from_notation(calling).unwrap();
+    state.apply_action((Some((0, 3)), Some(Piece::from_index(15))));
+    round_trip(&state);
+    let bytes = bincode::serialize(&state).unwrap();
+    assert!(bincode::deserialize::<State>(&bytes).unwrap().can_claim());
+    state.apply_action((None, None));
+    round_trip(&state);
+    let bytes = bincode::serialize(&state).unwrap();
+    assert!(bincode::deserialize::<State>(&bytes).unwrap().can_win());
+
+    let mut small = GameState::<3, 3>::new().with_rules("call".parse().unwrap());
+    while !small.is_done() {
+        small.apply_action(random_action(&small));
+        round_trip(&small);
+    }
+    let bytes = bincode::serialize(&State::new()).unwrap();
+    assert!(bincode::deserialize::<GameState<3, 3>>(&bytes).is_err());
+
+    let (record, _) = random_game();
+    let record = GameRecord {
+        rules: "misere".parse().unwrap(),
+        ..record
+    };
+    round_trip(&record);
+    let short = GameRecord {
+        actions: record.actions[..record.actions.len().min(12)].to_vec(),
+        ..record
+    };
+    round_trip(&Analysis::analyze(&short, 100));
+}
+
+#[test]
+fn records_and_reports() {
+    let (record, outcome) = random_game();
+    round_trip(&record);
+    round_trip(&outcome);
+    let state = State::from_notation("BSSFWSSF../BSTH.../..../.... WCTH").unwrap();
+    for statistics in mcts_search(&state, 500) {
+        round_trip(&statistics);
+    }
+    let short = GameRecord {
+        actions: record.actions[..record.actions.len().min(12)].to_vec(),
+        resigned: Some(1),
+        ..GameRecord::new()
+    };
+    round_trip(&Analysis::analyze(&short, 100));
+}
@@ -0,0 +1,125 @@
+use quarto::agents::random_action;
+use quarto::quarto::{RuleSet, State, Symmetry};
+use quarto::rng;
+use quarto::solver::{Solver, WIN};
+use quarto::tablebase::Tablebase;
+
+const EMPTY_LIMIT: usize = 4;
+
+fn path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!(
+            "quarto-tablebase-{}-{}.bin",
+            std::process::id(),
+            name
+        ))
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+// The positions of one random game from `root` on.
+fn descendants(root: State) -> Vec<State> {
+    let mut states = Vec::new();
+    let mut state = root;
+    while !state.is_done() {
+        states.push(state);
+        state.apply_action(random_action(&state));
+    }
+    states
+}
+
+#[test]
+fn probes_agree_with_the_solver() {
+    rng::seed(1);
+    let roots = Tablebase::sample_roots(EMPTY_LIMIT, 3);
+    let tablebase = Tablebase::from_roots(&roots, EMPTY_LIMIT);
+    assert!(!tablebase.is_empty());
+    let mut solver = Solver::new();
+    let mut probes = 0;
+    for &root in &roots {
+        assert!(root.empty_count() <= EMPTY_LIMIT);
+        for _ in 0..5 {
+            for state in descendants(root) {
+                let expected = solver.value(&state) as f64 / WIN as f64;
+                // Positions the symmetries map onto each other share their entry.
+                for symmetry in Symmetry::all().iter().step_by(997) {
+                    let image = state.transformed(symmetry);
+                    assert_eq!(
+                        tablebase.probe(&image),
+                        Some(expected),
+                        "{}",
+                        image.notation()
+                    );
+                    probes += 1;
+                }
+            }
+        }
+    }
+    assert!(probes > 0);
+    // The values are those of the classic rules.
+    let misere = roots[0].with_rules("misere".parse::<RuleSet>().unwrap());
+    assert_eq!(tablebase.probe(&misere), None);
+}
+
+#[test]
+fn probes_outside_the_sample_miss() {
+    rng::seed(2);
+    let tablebase = Tablebase::sample(EMPTY_LIMIT, 2);
+    let mut state = State::new();
+    while !state.is_done() && state.empty_count() > EMPTY_LIMIT {
+        assert_eq!(tablebase.probe(&state), None);
+        state.apply_action(random_action(&state));
+    }
+    // Another game's endgame is not in the sample.
+    let root = Tablebase::sample_roots(EMPTY_LIMIT, 1)[0];
+    assert_eq!(tablebase.probe(&root), None);
+}
+
+#[test]
+fn saved_tablebases_load_back() {
+    rng::seed(3);
+    let roots = Tablebase::sample_roots(EMPTY_LIMIT, 1);
+    let tablebase = Tablebase::from_roots(&roots, EMPTY_LIMIT);
+    let path = path("round-trip");
+    tablebase.save(&path).unwrap();
+    let loaded = Tablebase::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.len(), tablebase.len());
+    for state in descendants(roots[0]) {
+        assert_eq!(loaded.probe(&state), tablebase.probe(&state));
+    }
+}
+
+#[test]
+fn corrupt_tablebases_are_rejected() {
+    let header = |count: u64| {
+        let mut bytes = b"QTB1".to_vec();
+        bytes.push(EMPTY_LIMIT as u8);
+        bytes.extend(count.to_le_bytes());
+        bytes
+    };
+    let entry = |value: u8| {
+        let mut bytes = 1u128.to_le_bytes().to_vec();
+        bytes.push(value);
+        bytes
+    };
+    let path = path("corrupt");
+    let mut huge = header(u64::MAX / 2);
+    huge.extend(entry(WIN));
+    let mut short = header(2);
+    short.extend(entry(WIN));
+    let mut out_of_range = header(1);
+    out_of_range.extend(entry(WIN + 1));
+    for bytes in [huge, short, out_of_range, b"QTB0".to_vec()] {
+        std::fs::write(&path, bytes).unwrap();
+        assert!(Tablebase::load(&path).is_err());
+    }
+    let mut valid = header(1);
+    valid.extend(entry(WIN));
+    std::fs::write(&path, valid).unwrap();
+    let loaded = Tablebase::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap().len(), 1);
+}
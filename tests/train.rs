@@ -0,0 +1,95 @@
+use quarto::nn::Network;
+use quarto::rng;
+use quarto::selfplay::generate;
+use quarto::train::{gate, gradients, loss, split, train, TrainConfig, Trainer};
+
+#[test]
+fn gradients_match_finite_differences() {
+    rng::seed(1);
+    let samples = generate(1, 30, 1, 1);
+    let network = Network::random(&[8]);
+    let (gradient, _) = gradients(&network, &samples);
+    let epsilon = 1e-2;
+    for index in [0, 7, 130, 500] {
+        let mut plus = network.clone();
+        plus.trunk[0].weights[index] += epsilon;
+        let mut minus = network.clone();
+        minus.trunk[0].weights[index] -= epsilon;
+        let numeric =
+            (loss(&plus, &samples).total() - loss(&minus, &samples).total()) / (2.0 * epsilon);
+        let analytic = gradient.trunk[0].weights[index];
+        assert!(
+            (numeric - analytic).abs() < 1e-2,
+            "{} {}",
+            numeric,
+            analytic
+        );
+    }
+    for index in [0, 100, 2000] {
+        let mut plus = network.clone();
+        plus.policy.weights[index] += epsilon;
+        let mut minus = network.clone();
+        minus.policy.weights[index] -= epsilon;
+        let numeric =
+            (loss(&plus, &samples).total() - loss(&minus, &samples).total()) / (2.0 * epsilon);
+        let analytic = gradient.policy.weights[index];
+        assert!(
+            (numeric - analytic).abs() < 1e-2,
+            "{} {}",
+            numeric,
+            analytic
+        );
+    }
+}
+
+#[test]
+fn training_reduces_the_loss() {
+    rng::seed(2);
+    let samples = generate(4, 50, 2, 2);
+    let mut trainer = Trainer::new(Network::random(&[16]), 1e-2);
+    let before = loss(&trainer.network, &samples).total();
+    for _ in 0..20 {
+        trainer.epoch(&samples, 16);
+    }
+    assert!(loss(&trainer.network, &samples).total() < before);
+}
+
+#[test]
+fn validation_holds_out_whole_games() {
+    let samples = generate(10, 20, 2, 3);
+    let (training, validation) = split(&samples, 0.2);
+    assert_eq!(training.len() + validation.len(), samples.len());
+    assert!(training.iter().all(|s| s.game < 8));
+    assert!(validation.iter().all(|s| s.game >= 8));
+    assert!(split(&samples, 0.0).1.is_empty());
+}
+
+#[test]
+fn training_saves_checkpoints() {
+    rng::seed(4);
+    let samples = generate(4, 20, 2, 4);
+    let path = std::env::temp_dir()
+        .join(format!("quarto-train-{}", std::process::id()))
+        .to_str()
+        .unwrap()
+        .to_string();
+    let config = TrainConfig {
+        epochs: 2,
+        ..TrainConfig::default()
+    };
+    let (network, epochs) = train(Network::random(&[8]), &samples, &config, Some(&path)).unwrap();
+    assert_eq!(epochs.len(), 2);
+    for epoch in 1..=2 {
+        let checkpoint = format!("{}.{}", path, epoch);
+        Network::load(&checkpoint).unwrap();
+        std::fs::remove_file(checkpoint).unwrap();
+    }
+    let score = gate(&network, &Network::random(&[8]), 1, 20);
+    assert!((0.0..=1.0).contains(&score));
+
+    let missing = std::env::temp_dir()
+        .join("quarto-train-missing")
+        .join("weights");
+    let missing = missing.to_str().unwrap();
+    assert!(train(Network::random(&[8]), &samples, &config, Some(missing)).is_err());
+}
@@ -0,0 +1,349 @@
+use quarto::agents::{mcts_action, random_action};
+use quarto::quarto::{
+    GameState, Objective, Piece, RuleSet, Squares, State, Symmetry, WinningStatus,
+};
+use quarto::solver::{Solver, DRAW, LOSE, WIN};
+
+fn play_out<const N: usize, const K: usize>(mut state: GameState<N, K>) -> GameState<N, K> {
+    while !state.is_done() {
+        let action = random_action(&state);
+        assert!(state.is_legal_action(action));
+        state.apply_action(action);
+    }
+    state
+}
+
+#[test]
+fn lines_are_generated() {
+    assert_eq!(State::lines().len(), 10);
+    assert_eq!(GameState::<3, 3>::lines().len(), 8);
+    assert_eq!(GameState::<5, 5>::lines().len(), 12);
+    assert_eq!(GameState::<3, 3>::PIECE_NUMBER, 8);
+    assert_eq!(GameState::<3, 3>::new().legal_pieces().len(), 8);
+}
+
+#[test]
+fn small_games_end_when_the_pieces_run_out() {
+    for _ in 0..200 {
+        let state = play_out(GameState::<3, 3>::new());
+        assert!(state.turn() <= 8);
+        if !state.can_win() {
+            // Eight pieces leave one of the nine cells empty.
+            assert_eq!(state.empty_count(), 1);
+        }
+    }
+}
+
+#[test]
+fn large_games_use_every_attribute() {
+    for _ in 0..20 {
+        let state = play_out(GameState::<5, 5>::new());
+        let notation = state.notation();
+        assert_eq!(
+            GameState::<5, 5>::from_notation(&notation).unwrap(),
+            state,
+            "{}",
+            notation
+        );
+    }
+    assert_eq!(Piece::from_index(19).to_string(), "BSSH+1");
+    assert_eq!("BSSH+1".parse::<Piece>(), Ok(Piece::from_index(19)));
+    assert!("BSSH+0".parse::<Piece>().is_err());
+    assert!("BSSH+x".parse::<Piece>().is_err());
+}
+
+#[test]
+fn variants_reject_foreign_pieces() {
+    // `BSSF+1` has a fifth attribute, which the classic game does not have.
+    assert!(State::from_notation("BSSF+1.../..../..../.... BSTF").is_none());
+    assert!(GameState::<3, 3>::from_notation("WSSF../.../... BSTF").is_none());
+    assert!(GameState::<3, 3>::from_notation("BCSF../.../... BSTF").is_some());
+}
+
+// Plain minimax over every action, in half points for the player to move.
+fn minimax<const N: usize, const K: usize>(state: &GameState<N, K>) -> u8 {
+    let mut best = LOSE;
+    for action in state.legal_actions() {
+        let mut next_state = *state;
+        next_state.apply_action(action);
+        let value = match next_state.get_winning_status() {
+            WinningStatus::WIN => WIN,
+            WinningStatus::LOSE => LOSE,
+            WinningStatus::DRAW => DRAW,
+            WinningStatus::NONE => WIN - minimax(&next_state),
+        };
+        best = best.max(value);
+    }
+    best
+}
+
+#[test]
+fn three_by_three_is_solved() {
+    let mut solver = Solver::<3, 3>::new();
+    // The player who hands over the first piece loses.
+    assert_eq!(solver.value(&GameState::<3, 3>::new()), LOSE);
+    for _ in 0..20 {
+        let mut state = GameState::<3, 3>::new();
+        for _ in 0..4 {
+            if !state.is_done() {
+                state.apply_action(random_action(&state));
+            }
+        }
+        if !state.is_done() {
+            assert_eq!(
+                solver.value(&state),
+                minimax(&state),
+                "{}",
+                state.notation()
+            );
+        }
+    }
+}
+
+fn with_squares(notation: &str, squares: Squares) -> State {
+    State::from_notation(notation).unwrap().with_rules(RuleSet {
+        squares,
+        ..RuleSet::default()
+    })
+}
+
+#[test]
+fn blocks_win_with_squares() {
+    let notation = "BSSFBSSH../BSTF.../..../.... BSTH";
+    assert!(!with_squares(notation, Squares::Off).can_put_then_win(1, 1));
+    for squares in [Squares::Blocks, Squares::Toroidal] {
+        let mut state = with_squares(notation, squares);
+        assert!(state.can_put_then_win(1, 1));
+        state.apply_action((Some((1, 1)), None));
+        assert!(state.is_done());
+        assert_eq!(
+            state.winning_lines(),
+            [vec![(0, 0), (0, 1), (1, 0), (1, 1)]]
+        );
+    }
+}
+
+#[test]
+fn toroidal_squares_wrap_around() {
+    // The four corners, and two cells of the top row with two of the bottom row.
+    for (notation, place) in [
+        ("BSSF..BSSH/..../..../BSTF... BSTH", (3, 3)),
+        (".BSSFBSSH./..../..../.BSTF.. BSTH", (3, 2)),
+    ] {
+        assert!(!with_squares(notation, Squares::Blocks).can_put_then_win(place.0, place.1));
+        assert!(with_squares(notation, Squares::Toroidal).can_put_then_win(place.0, place.1));
+    }
+}
+
+#[test]
+fn symmetries_keep_the_squares() {
+    let count = |squares| {
+        Symmetry::board_symmetries()
+            .iter()
+            .filter(|symmetry| {
+                symmetry.keeps_patterns(RuleSet {
+                    squares,
+                    ..RuleSet::default()
+                })
+            })
+            .count()
+    };
+    assert_eq!(count(Squares::Off), 32);
+    // Only rotations and reflections keep the blocks. Wrapping blocks are also kept by swapping
+    // the first two rows with the last two, and the first row of each pair with the second.
+    assert_eq!(count(Squares::Blocks), 8);
+    assert_eq!(count(Squares::Toroidal), 16);
+}
+
+#[test]
+fn engines_respect_the_rules() {
+    let state = with_squares("BSSFBSSH../BSTF.../..../.... BSTH", Squares::Blocks);
+    assert_eq!(mcts_action(&state, 2000).0, Some((1, 1)));
+
+    let mut solver = Solver::new();
+    for squares in [Squares::Blocks, Squares::Toroidal] {
+        for _ in 0..5 {
+            let mut state = State::new().with_rules(RuleSet {
+                squares,
+                ..RuleSet::default()
+            });
+            while !state.is_done() && state.empty_count() > 6 {
+                state.apply_action(random_action(&state));
+            }
+            if !state.is_done() {
+                assert_eq!(
+                    solver.value(&state),
+                    minimax(&state),
+                    "{}",
+                    state.notation()
+                );
+            }
+        }
+    }
+}
+
+fn calling(notation: &str) -> State {
+    State::from_notation(notation).unwrap().with_rules(RuleSet {
+        quarto_call: true,
+        ..RuleSet::default()
+    })
+}
+
+#[test]
+fn missed_wins_can_be_claimed() {
+    // Black pieces fill the top row at d1.
+    let mut state = calling("BSSFBSSHBSTF./..../..../.... BSTH");
+    let miss = (Some((0, 3)), Some(Piece::from_index(15)));
+    assert!(state.legal_actions().contains(&(Some((0, 3)), None)));
+    assert!(state.legal_actions().contains(&miss));
+    assert!(!state.legal_actions().contains(&(None, None)));
+
+    state.apply_action(miss);
+    assert!(!state.is_done());
+    assert!(!state.can_win());
+    let mover = state.is_first_player();
+    assert!(state.can_claim());
+    assert!(state.is_legal_action((None, None)));
+    assert_eq!(random_action(&state), (None, None));
+    assert_eq!(mcts_action(&state, 100), (None, None));
+    assert_eq!(Solver::new().value(&state), WIN);
+
+    state.apply_action((None, None));
+    assert!(state.is_done());
+    assert_eq!(state.get_winning_status(), WinningStatus::WIN);
+    assert_eq!(state.is_first_player(), mover);
+}
+
+#[test]
+fn claims_expire_with_the_next_placement() {
+    let mut state = calling("BSSFBSSHBSTF./..../..../.... BSTH");
+    state.apply_action((Some((0, 3)), Some(Piece::from_index(15))));
+    let place = state.legal_placements()[0];
+    let piece = Piece::from_index(14);
+    state.apply_action((Some(place), Some(piece)));
+    assert!(!state.can_claim());
+    assert!(!state.is_legal_action((None, None)));
+    // The completed top row no longer wins for anyone.
+    assert!(!state.can_win());
+}
+
+#[test]
+fn notations_keep_the_rules_and_open_lines() {
+    let mut state = calling("BSSFBSSHBSTF./..../..../.... BSTH");
+    state.apply_action((Some((0, 3)), Some(Piece::from_index(15))));
+    let notation = state.notation();
+    assert!(notation.ends_with(" call open"), "{}", notation);
+    assert_eq!(State::from_notation(&notation), Some(state));
+    // Once the claim has expired the line no longer counts.
+    let expired = State::from_notation(notation.strip_suffix(" open").unwrap()).unwrap();
+    assert!(!expired.can_claim());
+    assert!(!expired.is_done());
+    let board = notation.strip_suffix(" call open").unwrap();
+    assert!(State::from_notation(board).unwrap().is_done());
+    assert!(State::from_notation(&format!("{} open", board)).is_none());
+    assert!(State::from_notation("BSSFBSSH../..../..../.... BSTH call open").is_none());
+
+    state.apply_action((None, None));
+    assert!(state.notation().ends_with(" call won"));
+    assert_eq!(State::from_notation(&state.notation()), Some(state));
+
+    for spec in ["misere", "squares,call", "toroidal,call,scored"] {
+        let mut state = State::new().with_rules(spec.parse().unwrap());
+        loop {
+            let notation = state.notation();
+            assert!(notation.contains(spec), "{}", notation);
+            assert_eq!(State::from_notation(&notation), Some(state), "{}", notation);
+            if state.is_done() {
+                break;
+            }
+            state.apply_action(random_action(&state));
+        }
+    }
+}
+
+#[test]
+fn wins_are_called_without_the_rule() {
+    let state = State::from_notation("BSSFBSSHBSTF./..../..../.... BSTH").unwrap();
+    assert!(!state.is_legal_action((Some((0, 3)), Some(Piece::from_index(15)))));
+    assert!(!state.is_legal_action((None, None)));
+    assert_eq!(
+        state.normalize_action((Some((0, 3)), Some(Piece::from_index(15)))),
+        (Some((0, 3)), None)
+    );
+    assert_eq!(RuleSet::default().to_string(), "classic");
+    for spec in [
+        "classic",
+        "call",
+        "squares,call",
+        "toroidal,misere",
+        "scored",
+    ] {
+        assert_eq!(spec.parse::<RuleSet>().unwrap().to_string(), spec);
+    }
+    assert!("chess".parse::<RuleSet>().is_err());
+    assert!("call,misere".parse::<RuleSet>().is_err());
+}
+
+fn with_objective(notation: &str, objective: Objective) -> State {
+    State::from_notation(notation).unwrap().with_rules(RuleSet {
+        objective,
+        ..RuleSet::default()
+    })
+}
+
+#[test]
+fn misere_lines_lose() {
+    let mut state = with_objective("BSSFBSSHBSTF./..../..../.... BSTH", Objective::Misere);
+    assert_ne!(mcts_action(&state, 1000).0, Some((0, 3)));
+    let first_player = state.is_first_player();
+    state.apply_action((Some((0, 3)), None));
+    assert_eq!(state.get_winning_status(), WinningStatus::LOSE);
+    assert_eq!(state.reward(), 0.0);
+    assert_eq!(
+        state.get_first_player_score_for_win_rate(),
+        if first_player { 0.0 } else { 1.0 }
+    );
+
+    let rules = RuleSet {
+        objective: Objective::Misere,
+        ..RuleSet::default()
+    };
+    let mut solver = Solver::<3, 3>::new();
+    for _ in 0..20 {
+        let mut state = GameState::<3, 3>::new().with_rules(rules);
+        for _ in 0..4 {
+            if !state.is_done() {
+                state.apply_action(random_action(&state));
+            }
+        }
+        if !state.is_done() {
+            assert_eq!(
+                solver.value(&state),
+                minimax(&state),
+                "{}",
+                state.notation()
+            );
+        }
+    }
+}
+
+#[test]
+fn scored_wins_count_shared_attributes() {
+    // Four different pieces share at most two of the four attributes.
+    let points = |notation: &str| {
+        let mut state = with_objective(notation, Objective::Scored);
+        state.apply_action((Some((0, 3)), None));
+        assert_eq!(state.get_winning_status(), WinningStatus::WIN);
+        (state.line_score(), state.reward())
+    };
+    assert_eq!(points("BSSFBSSHBSTF./..../..../.... BSTH"), (2, 0.75));
+    assert_eq!(points("BSSFWCSFBCTF./..../..../.... WSTF"), (1, 0.625));
+
+    let mut state = with_objective("BSSFBSSHBSTF./..../..../.... BSTH", Objective::Win);
+    state.apply_action((Some((0, 3)), None));
+    assert_eq!(state.reward(), 1.0);
+    assert_eq!(
+        "scored".parse::<RuleSet>().unwrap().objective,
+        Objective::Scored
+    );
+}